@@ -0,0 +1,110 @@
+use actix_raknet::{
+    packetqueue::PacketQueue,
+    packets::{Frame, FrameSet, Reliability},
+    send::Priority,
+};
+use bytes::{Bytes, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+const PLAYERS: usize = 200;
+
+const IN_FLIGHT: usize = 64;
+
+fn frame_set() -> FrameSet {
+    let datas = (0..4)
+        .map(|i| {
+            let mut frame = Frame::new(Reliability::ReliableOrdered, vec![0xfe; 300]);
+            frame.message_index = i;
+            frame.order_index = i;
+            frame
+        })
+        .collect();
+    FrameSet {
+        header: 0x84,
+        sequence_number: 0,
+        datas,
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    let datagram = frame_set().encode().freeze();
+    c.bench_function("frame_set/decode_copy", |b| {
+        b.iter(|| FrameSet::decode(black_box(&datagram[..])).unwrap())
+    });
+    c.bench_function("frame_set/decode_bytes", |b| {
+        b.iter(|| FrameSet::decode_bytes(black_box(&datagram)).unwrap())
+    });
+}
+
+fn encode(c: &mut Criterion) {
+    let frame_set = frame_set();
+    c.bench_function("frame_set/encode", |b| {
+        b.iter(|| black_box(&frame_set).encode())
+    });
+    c.bench_function("frame_set/clone", |b| {
+        b.iter(|| black_box(&frame_set).clone())
+    });
+}
+
+fn sent_queue(reliability: Reliability) -> PacketQueue {
+    let mut queue = PacketQueue::new(1400);
+    for _ in 0..IN_FLIGHT {
+        queue.add_frame(
+            Frame::new(Reliability::Reliable, vec![0xfe; 600]),
+            None,
+            Priority::Medium,
+        );
+        queue.add_frame(
+            Frame::new(reliability.clone(), vec![0xfe; 600]),
+            None,
+            Priority::Medium,
+        );
+    }
+    queue.flush();
+    queue.take_sendable(|_| true);
+    queue
+}
+
+fn resend(c: &mut Criterion) {
+    let nack = |mut queue: PacketQueue| {
+        for sequence in 0..IN_FLIGHT as u32 {
+            queue.resend(sequence);
+        }
+        queue.take_sendable(|_| true)
+    };
+    c.bench_function("packet_queue/resend_cached", |b| {
+        b.iter_batched(
+            || sent_queue(Reliability::Reliable),
+            nack,
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("packet_queue/resend_encode", |b| {
+        b.iter_batched(
+            || sent_queue(Reliability::Unreliable),
+            nack,
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn fan_out(c: &mut Criterion) {
+    let payload = Bytes::from(vec![0x19; 1200]);
+    c.bench_function("broadcast/copy", |b| {
+        b.iter(|| {
+            (0..PLAYERS)
+                .map(|_| BytesMut::from(&black_box(&payload)[..]))
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("broadcast/share", |b| {
+        b.iter(|| {
+            (0..PLAYERS)
+                .map(|_| black_box(&payload).clone())
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, decode, encode, resend, fan_out);
+criterion_main!(benches);
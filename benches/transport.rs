@@ -0,0 +1,172 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    batch::BatchConfig,
+    client::{RakClient, RakClientEvent},
+    send::SendOptions,
+    server::{ConnectionHandle, RakServer, RakServerEvent},
+};
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::channel::oneshot;
+
+const PACKETS: usize = 64;
+const SIZE: usize = 1000;
+
+type Pending = Arc<Mutex<Option<(usize, oneshot::Sender<()>)>>>;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Std,
+    Batched(BatchConfig),
+}
+
+struct Client {
+    pending: Pending,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(_) = msg {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some((remaining, _)) = pending.as_mut() {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    let _ = pending.take().unwrap().1.send(());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Blast;
+
+struct Server {
+    ready: Option<oneshot::Sender<()>>,
+    handle: Option<ConnectionHandle>,
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            self.handle = Some(handle);
+            if let Some(ready) = self.ready.take() {
+                let _ = ready.send(());
+            }
+        }
+    }
+}
+
+impl Handler<Blast> for Server {
+    type Result = ();
+    fn handle(&mut self, _msg: Blast, _ctx: &mut Self::Context) -> Self::Result {
+        let handle = self.handle.as_ref().unwrap();
+        let payload = Bytes::from(vec![0xfe; SIZE]);
+        for _ in 0..PACKETS {
+            handle.send_with(payload.clone(), SendOptions::new());
+        }
+        handle.flush();
+    }
+}
+
+async fn setup(kind: Kind, pending: Pending) -> Addr<Server> {
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr: SocketAddr = server_socket.local_addr().unwrap();
+    let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let (ready, connected) = oneshot::channel();
+    let server = Server::create(move |ctx| {
+        match kind {
+            Kind::Std => {
+                let socket = tokio::net::UdpSocket::from_std(server_socket).unwrap();
+                RakServer::new(socket, 0x1919, "bench".to_owned(), ctx.address(), 1);
+            }
+            Kind::Batched(config) => {
+                RakServer::new_batched(
+                    server_socket,
+                    0x1919,
+                    "bench".to_owned(),
+                    ctx.address(),
+                    1,
+                    config,
+                )
+                .unwrap();
+            }
+        }
+        Server {
+            ready: Some(ready),
+            handle: None,
+        }
+    });
+    Client::create(move |ctx| {
+        let system = System::current();
+        let arbiter = system.arbiter();
+        let rak_client = match kind {
+            Kind::Std => {
+                let socket = tokio::net::UdpSocket::from_std(client_socket).unwrap();
+                RakClient::init(socket, 114514, ctx.address(), arbiter)
+            }
+            Kind::Batched(config) => {
+                RakClient::init_batched(client_socket, 114514, ctx.address(), arbiter, config)
+                    .unwrap()
+            }
+        };
+        rak_client.connect(server_addr);
+        Client { pending }
+    });
+    connected.await.unwrap();
+    server
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut runner = System::new("transport");
+    let mut group = c.benchmark_group("transport");
+    group
+        .sample_size(20)
+        .throughput(Throughput::Bytes((PACKETS * SIZE) as u64));
+    let kinds = [
+        ("std", Kind::Std),
+        ("batched", Kind::Batched(BatchConfig::new(32, false))),
+        ("batched_gso", Kind::Batched(BatchConfig::new(32, true))),
+    ];
+    for (name, kind) in kinds {
+        let pending = Pending::default();
+        let server = runner.block_on(setup(kind, pending.clone()));
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let pending = pending.clone();
+                let server = server.clone();
+                runner.block_on(async move {
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        let (done, received) = oneshot::channel();
+                        *pending.lock().unwrap() = Some((PACKETS, done));
+                        server.send(Blast).await.unwrap();
+                        received.await.unwrap();
+                    }
+                    start.elapsed()
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
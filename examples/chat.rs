@@ -3,6 +3,7 @@ use std::{collections::HashMap, io::stdin, net::SocketAddr};
 use actix::prelude::*;
 use actix_raknet::{
     client::{ClientHandle, RakClient, RakClientEvent},
+    send::SendOptions,
     server::{ConnectionHandle, RakServer, RakServerEvent},
 };
 use bytes::BytesMut;
@@ -71,7 +72,7 @@ impl Handler<RakServerEvent> for Server {
             RakServerEvent::Packet(_, b) => {
                 dbg!();
                 for conn in self.conns.iter() {
-                    conn.1.send(b.clone());
+                    conn.1.send_with(b.clone(), SendOptions::new());
                 }
             }
             RakServerEvent::Disconnected(addr, _guid) => {
@@ -128,7 +129,7 @@ fn client() {
             loop {
                 s_buffer.clear();
                 stdin.read_line(&mut s_buffer).unwrap();
-                let line = s_buffer.replace(|x| x == '\n' || x == '\r', "");
+                let line = s_buffer.replace(['\n', '\r'], "");
                 let bytes = BytesMut::from(line.as_bytes());
                 client1.do_send(Packet(bytes));
             }
@@ -38,6 +38,6 @@ async fn main() {
         .unwrap()
         .collect::<Vec<SocketAddr>>();
 
-    handler.do_send(PingTo(remote.first().unwrap().clone()));
+    handler.do_send(PingTo(*remote.first().unwrap()));
     actix_rt::Arbiter::local_join().await;
 }
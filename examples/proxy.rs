@@ -1,9 +1,10 @@
 use actix::prelude::*;
 use actix_raknet::{
     client::{ClientHandle, RakClient, RakClientEvent},
+    send::SendOptions,
     server::{ConnectionHandle, RakServer, RakServerEvent},
 };
-use bytes::BytesMut;
+use bytes::Bytes;
 use futures::executor::block_on;
 use std::net::SocketAddr;
 
@@ -46,7 +47,7 @@ impl Handler<ClientOrder> for Client {
                 self.rak_client.disconnect();
             }
             ClientOrder::Packet(buff) => {
-                self.rak_client.packet(buff);
+                self.rak_client.packet_with(buff, SendOptions::new());
             }
         }
     }
@@ -57,14 +58,14 @@ impl Handler<ClientOrder> for Client {
 enum ClientOrder {
     Connect(SocketAddr),
     Disconnect,
-    Packet(BytesMut),
+    Packet(Bytes),
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 enum ServerOrder {
     Disconnect,
-    Packet(BytesMut),
+    Packet(Bytes),
 }
 
 struct Server {
@@ -129,7 +130,10 @@ impl Handler<ServerOrder> for Server {
                 self.handle.as_ref().unwrap().disconnect();
             }
             ServerOrder::Packet(p) => {
-                self.handle.as_ref().unwrap().send(p);
+                self.handle
+                    .as_ref()
+                    .unwrap()
+                    .send_with(p, SendOptions::new());
             }
         }
     }
@@ -0,0 +1,507 @@
+use std::{
+    collections::VecDeque,
+    io, mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    os::unix::io::AsRawFd,
+    ptr,
+    rc::Rc,
+    sync::Arc,
+    task::Poll,
+};
+
+use actix::{dev::ToEnvelope, prelude::*};
+use bytes::BytesMut;
+use futures::future::poll_fn;
+use mio::{unix::EventedFd, Evented, PollOpt, Ready, Token};
+use tokio::io::PollEvented;
+
+use crate::{
+    capture::{Capture, SetCapture},
+    macros::warning,
+    metrics::ServerMetrics,
+    udp::{ReceivedUdp, SendUdp, UdpPacket},
+};
+
+const MAX_DATAGRAM: usize = 2048;
+
+const MAX_SEGMENTS: usize = 64;
+
+const MAX_GSO_BYTES: usize = 65000;
+
+const UDP_SEGMENT: libc::c_int = 103;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchConfig {
+    pub batch: usize,
+    pub gso: bool,
+}
+
+impl BatchConfig {
+    pub fn new(batch: usize, gso: bool) -> Self {
+        assert!(batch > 0, "batch size must be positive");
+        Self { batch, gso }
+    }
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self::new(32, false)
+    }
+}
+
+struct Socket(UdpSocket);
+
+impl Evented for Socket {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).register(poll, token, interest, opts)
+    }
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).deregister(poll)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ReceivedBatch(Vec<UdpPacket>);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct SocketErr(io::Error);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ReadinessErr(io::Error);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Flush;
+
+pub(crate) struct BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    socket: Option<UdpSocket>,
+    evented: Option<Rc<PollEvented<Socket>>>,
+    outbox: VecDeque<UdpPacket>,
+    flushing: bool,
+    config: BatchConfig,
+    recv_handle: Option<SpawnHandle>,
+    handler: Addr<T>,
+    metrics: Option<Arc<ServerMetrics>>,
+    local: SocketAddr,
+    capture: Option<Capture>,
+}
+
+impl<T> BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    pub fn new(
+        socket: UdpSocket,
+        config: BatchConfig,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Addr<Self> {
+        let local = socket
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        Self::start_in_arbiter(arbiter, move |_ctx| Self {
+            socket: Some(socket),
+            evented: None,
+            outbox: VecDeque::new(),
+            flushing: false,
+            config,
+            recv_handle: None,
+            handler,
+            metrics,
+            local,
+            capture: None,
+        })
+    }
+    fn flush(&mut self, ctx: &mut Context<Self>) {
+        let evented = match &self.evented {
+            Some(evented) => evented.clone(),
+            None => return,
+        };
+        while !self.outbox.is_empty() {
+            match send_batch(&evented.get_ref().0, &mut self.outbox, &self.config) {
+                Ok(()) => {}
+                Err((e, _)) if e.kind() == io::ErrorKind::WouldBlock => {
+                    let mut cleared = false;
+                    let writable = poll_fn(move |cx| {
+                        if !cleared {
+                            cleared = true;
+                            evented.clear_write_ready(cx)?;
+                        }
+                        evented.poll_write_ready(cx).map_ok(|_| ())
+                    });
+                    ctx.spawn(writable.into_actor(self).map(|_, me, ctx| me.flush(ctx)));
+                    return;
+                }
+                Err((_e, segments)) if self.config.gso && segments > 1 => {
+                    warning!(error = %_e, "udp segmentation offload failed, disabling");
+                    self.config.gso = false;
+                }
+                Err((e, segments)) => {
+                    self.socket_error(e);
+                    self.outbox.drain(..segments);
+                    if let Some(metrics) = &self.metrics {
+                        metrics.dropped(segments as u64);
+                    }
+                }
+            }
+        }
+    }
+    fn socket_error(&self, _e: io::Error) {
+        warning!(error = %_e, "udp socket error");
+        if let Some(metrics) = &self.metrics {
+            metrics.socket_error();
+        }
+    }
+}
+
+impl<T> Actor for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let socket = self.socket.take().unwrap();
+        let evented = match PollEvented::new(Socket(socket)) {
+            Ok(evented) => Rc::new(evented),
+            Err(e) => {
+                self.socket_error(e);
+                ctx.stop();
+                return;
+            }
+        };
+        self.evented = Some(evented.clone());
+        let batch = self.config.batch;
+        let address = ctx.address();
+        let udp_future = async move {
+            let mut buff = BytesMut::new();
+            loop {
+                let ready = poll_fn(|cx| evented.poll_read_ready(cx, Ready::readable())).await;
+                // a failed readiness poll does not recover, retrying would spin
+                if let Err(e) = ready {
+                    address.do_send(ReadinessErr(e));
+                    break;
+                }
+                match recv_batch(&evented.get_ref().0, &mut buff, batch) {
+                    Ok(packets) => address.do_send(ReceivedBatch(packets)),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        let cleared = poll_fn(|cx| {
+                            Poll::Ready(evented.clear_read_ready(cx, Ready::readable()))
+                        })
+                        .await;
+                        if let Err(e) = cleared {
+                            address.do_send(SocketErr(e));
+                        }
+                    }
+                    Err(e) => address.do_send(SocketErr(e)),
+                }
+            }
+        }
+        .into_actor(self);
+        self.recv_handle = Some(ctx.spawn(udp_future));
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        if let Some(handle) = self.recv_handle {
+            ctx.cancel_future(handle);
+        }
+    }
+}
+
+impl<T> Handler<ReceivedBatch> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: ReceivedBatch, _ctx: &mut Self::Context) -> Self::Result {
+        for packet in msg.0 {
+            if let Some(capture) = &self.capture {
+                capture.record(packet.addr, self.local, &packet.bytes);
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.received(packet.bytes.len());
+            }
+            self.handler.do_send(ReceivedUdp(packet))
+        }
+    }
+}
+
+impl<T> Handler<SocketErr> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SocketErr, _ctx: &mut Self::Context) -> Self::Result {
+        self.socket_error(msg.0);
+    }
+}
+
+impl<T> Handler<ReadinessErr> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: ReadinessErr, ctx: &mut Self::Context) -> Self::Result {
+        self.socket_error(msg.0);
+        ctx.stop();
+    }
+}
+
+impl<T> Handler<SendUdp> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SendUdp, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(capture) = &self.capture {
+            capture.record(self.local, msg.0.addr, &msg.0.bytes);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.sent(msg.0.bytes.len());
+        }
+        self.outbox.push_back(msg.0);
+        if !self.flushing {
+            self.flushing = true;
+            ctx.notify(Flush);
+        }
+    }
+}
+
+impl<T> Handler<Flush> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, _msg: Flush, ctx: &mut Self::Context) -> Self::Result {
+        self.flushing = false;
+        self.flush(ctx);
+    }
+}
+
+impl<T> Handler<SetCapture> for BatchUdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetCapture, _ctx: &mut Self::Context) -> Self::Result {
+        self.capture = msg.0;
+    }
+}
+
+fn recv_batch(socket: &UdpSocket, buff: &mut BytesMut, batch: usize) -> io::Result<Vec<UdpPacket>> {
+    buff.clear();
+    buff.reserve(batch * MAX_DATAGRAM);
+    buff.resize(batch * MAX_DATAGRAM, 0);
+    let mut names: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; batch];
+    let mut iovecs = buff
+        .chunks_mut(MAX_DATAGRAM)
+        .map(|x| libc::iovec {
+            iov_base: x.as_mut_ptr() as *mut libc::c_void,
+            iov_len: x.len(),
+        })
+        .collect::<Vec<_>>();
+    let mut headers = names
+        .iter_mut()
+        .zip(iovecs.iter_mut())
+        .map(|(name, iovec)| {
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_name = name as *mut _ as *mut libc::c_void;
+            header.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as u32;
+            header.msg_hdr.msg_iov = iovec;
+            header.msg_hdr.msg_iovlen = 1;
+            header
+        })
+        .collect::<Vec<_>>();
+    let received = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            headers.as_mut_ptr(),
+            batch as libc::c_uint,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if received < 0 {
+        buff.clear();
+        return Err(io::Error::last_os_error());
+    }
+    let mut packets = Vec::with_capacity(received as usize);
+    for (header, name) in headers.iter().zip(names.iter()).take(received as usize) {
+        let mut bytes = buff.split_to(MAX_DATAGRAM);
+        bytes.truncate(header.msg_len as usize);
+        if let (false, Some(addr)) = (bytes.is_empty(), to_socket_addr(name)) {
+            packets.push(UdpPacket {
+                bytes: bytes.freeze(),
+                addr,
+            });
+        }
+    }
+    buff.clear();
+    Ok(packets)
+}
+
+fn send_batch(
+    socket: &UdpSocket,
+    outbox: &mut VecDeque<UdpPacket>,
+    config: &BatchConfig,
+) -> Result<(), (io::Error, usize)> {
+    let mut groups: Vec<(usize, usize, usize)> = vec![];
+    let mut start = 0;
+    while start < outbox.len() && groups.len() < config.batch {
+        let segment = outbox[start].bytes.len();
+        let mut end = start + 1;
+        if config.gso {
+            let mut total = segment;
+            while end < outbox.len()
+                && end - start < MAX_SEGMENTS
+                && outbox[end].addr == outbox[start].addr
+                && outbox[end].bytes.len() <= segment
+                && total + outbox[end].bytes.len() <= MAX_GSO_BYTES
+            {
+                total += outbox[end].bytes.len();
+                end += 1;
+                if outbox[end - 1].bytes.len() < segment {
+                    break;
+                }
+            }
+        }
+        groups.push((start, end, segment));
+        start = end;
+    }
+    let mut iovecs = outbox
+        .iter()
+        .take(start)
+        .map(|x| libc::iovec {
+            iov_base: x.bytes.as_ptr() as *mut libc::c_void,
+            iov_len: x.bytes.len(),
+        })
+        .collect::<Vec<_>>();
+    let mut names = groups
+        .iter()
+        .map(|x| to_sockaddr(&outbox[x.0].addr))
+        .collect::<Vec<_>>();
+    let mut controls = vec![[0u64; 4]; groups.len()];
+    let mut headers = Vec::with_capacity(groups.len());
+    for (i, (start, end, segment)) in groups.iter().enumerate() {
+        let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+        header.msg_hdr.msg_name = &mut names[i].0 as *mut _ as *mut libc::c_void;
+        header.msg_hdr.msg_namelen = names[i].1;
+        header.msg_hdr.msg_iov = &mut iovecs[*start];
+        header.msg_hdr.msg_iovlen = end - start;
+        if end - start > 1 {
+            let space = unsafe { libc::CMSG_SPACE(mem::size_of::<u16>() as u32) } as usize;
+            header.msg_hdr.msg_control = controls[i].as_mut_ptr() as *mut libc::c_void;
+            header.msg_hdr.msg_controllen = space;
+            unsafe {
+                let cmsg = libc::CMSG_FIRSTHDR(&header.msg_hdr);
+                (*cmsg).cmsg_level = libc::SOL_UDP;
+                (*cmsg).cmsg_type = UDP_SEGMENT;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as u32) as usize;
+                ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, *segment as u16);
+            }
+        }
+        headers.push(header);
+    }
+    let sent = unsafe {
+        libc::sendmmsg(
+            socket.as_raw_fd(),
+            headers.as_mut_ptr(),
+            headers.len() as libc::c_uint,
+            0,
+        )
+    };
+    let (_, first, _) = groups[0];
+    if sent < 0 {
+        return Err((io::Error::last_os_error(), first));
+    }
+    if sent == 0 {
+        return Err((io::ErrorKind::WouldBlock.into(), first));
+    }
+    let (_, end, _) = groups[sent as usize - 1];
+    outbox.drain(..end);
+    Ok(())
+}
+
+fn to_socket_addr(name: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match name.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in) };
+            Some(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                u16::from_be(addr.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in6) };
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+fn to_sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = unsafe { &mut *(&mut name as *mut _ as *mut libc::sockaddr_in) };
+            raw.sin_family = libc::AF_INET as libc::sa_family_t;
+            raw.sin_port = addr.port().to_be();
+            raw.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = unsafe { &mut *(&mut name as *mut _ as *mut libc::sockaddr_in6) };
+            raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            raw.sin6_port = addr.port().to_be();
+            raw.sin6_addr.s6_addr = addr.ip().octets();
+            raw.sin6_flowinfo = addr.flowinfo();
+            raw.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (name, len as libc::socklen_t)
+}
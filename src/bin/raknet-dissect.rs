@@ -0,0 +1,57 @@
+use std::io::{self, BufRead, Result};
+
+use actix_raknet::{
+    capture::PcapReader,
+    inspect::{dissect, parse_hex},
+};
+
+const USAGE: &str = "usage: raknet-dissect <capture.pcap>
+       raknet-dissect --hex <hex bytes>...
+       raknet-dissect          (reads one hex datagram per line from stdin)";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(|x| x.as_str()) {
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some("--hex") => dissect_hex(&args[1..].join(" ")),
+        Some(path) => dissect_pcap(path),
+        None => dissect_stdin(),
+    };
+    if let Err(e) = result {
+        eprintln!("raknet-dissect: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn dissect_hex(text: &str) -> Result<()> {
+    println!("{}", dissect(&parse_hex(text)?));
+    Ok(())
+}
+
+fn dissect_stdin() -> Result<()> {
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            dissect_hex(&line)?;
+        }
+    }
+    Ok(())
+}
+
+fn dissect_pcap(path: &str) -> Result<()> {
+    for datagram in PcapReader::open(path)?.datagrams()? {
+        println!(
+            "{}.{:06} {} -> {} length={}",
+            datagram.time.as_secs(),
+            datagram.time.subsec_micros(),
+            datagram.source,
+            datagram.destination,
+            datagram.payload.len()
+        );
+        println!("{}\n", dissect(&datagram.payload));
+    }
+    Ok(())
+}
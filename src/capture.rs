@@ -0,0 +1,390 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Result, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix::prelude::*;
+use bytes::BytesMut;
+
+use crate::{
+    reader::{Endian, Reader},
+    transport::MemoryNetwork,
+    writer::Writer,
+};
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const SNAPLEN: u32 = 65535;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const IPPROTO_UDP: u8 = 17;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetCapture(pub Option<Capture>);
+
+#[derive(Clone)]
+pub struct Capture {
+    writer: Arc<Mutex<PcapWriter>>,
+}
+
+impl Capture {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::create(path)?;
+        Self::new(BufWriter::new(file))
+    }
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Result<Self> {
+        Ok(Self {
+            writer: Arc::new(Mutex::new(PcapWriter::new(Box::new(writer))?)),
+        })
+    }
+    pub fn flush(&self) -> Result<()> {
+        self.writer.lock().unwrap().inner.flush()
+    }
+    pub(crate) fn record(&self, source: SocketAddr, destination: SocketAddr, payload: &[u8]) {
+        let mut writer = self.writer.lock().unwrap();
+        if writer
+            .record(SystemTime::now(), source, destination, payload)
+            .is_err()
+        {
+            crate::macros::warning!("failed to write capture record");
+        }
+    }
+}
+
+struct PcapWriter {
+    inner: Box<dyn Write + Send>,
+}
+
+impl PcapWriter {
+    fn new(mut inner: Box<dyn Write + Send>) -> Result<Self> {
+        let mut header = BytesMut::new();
+        let mut cursor = Writer::new(&mut header);
+        cursor.write_u32(PCAP_MAGIC, Endian::Little);
+        cursor.write_u16(2, Endian::Little);
+        cursor.write_u16(4, Endian::Little);
+        cursor.write_u32(0, Endian::Little);
+        cursor.write_u32(0, Endian::Little);
+        cursor.write_u32(SNAPLEN, Endian::Little);
+        cursor.write_u32(LINKTYPE_RAW, Endian::Little);
+        inner.write_all(&header)?;
+        Ok(Self { inner })
+    }
+
+    fn record(
+        &mut self,
+        time: SystemTime,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) -> Result<()> {
+        let packet = ip_packet(source, destination, payload);
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut record = BytesMut::new();
+        let mut cursor = Writer::new(&mut record);
+        cursor.write_u32(since_epoch.as_secs() as u32, Endian::Little);
+        cursor.write_u32(since_epoch.subsec_micros(), Endian::Little);
+        cursor.write_u32(packet.len() as u32, Endian::Little);
+        cursor.write_u32(packet.len() as u32, Endian::Little);
+        cursor.write(&packet);
+        self.inner.write_all(&record)
+    }
+}
+
+fn ipv6_of(address: IpAddr) -> Ipv6Addr {
+    match address {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+fn checksum(data: &[u8], mut sum: u32) -> u16 {
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            u16::from_be_bytes([chunk[0], chunk[1]])
+        } else {
+            u16::from_be_bytes([chunk[0], 0])
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn pseudo_sum(source: &[u8], destination: &[u8], length: u32) -> u32 {
+    let mut sum = 0u32;
+    for chunk in source.chunks(2).chain(destination.chunks(2)) {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    sum + IPPROTO_UDP as u32 + (length >> 16) + (length & 0xffff)
+}
+
+fn ip_packet(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> BytesMut {
+    let udp_len = 8 + payload.len();
+    let mut udp = BytesMut::new();
+    let mut cursor = Writer::new(&mut udp);
+    cursor.write_u16(source.port(), Endian::Big);
+    cursor.write_u16(destination.port(), Endian::Big);
+    cursor.write_u16(udp_len as u16, Endian::Big);
+    cursor.write_u16(0, Endian::Big);
+    cursor.write(payload);
+
+    let mut packet = BytesMut::new();
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let sum = checksum(
+                &udp,
+                pseudo_sum(&src.octets(), &dst.octets(), udp_len as u32),
+            );
+            udp[6..8].copy_from_slice(&sum.to_be_bytes());
+
+            let mut header = BytesMut::new();
+            let mut cursor = Writer::new(&mut header);
+            cursor.write_u8(0x45);
+            cursor.write_u8(0);
+            cursor.write_u16((20 + udp_len) as u16, Endian::Big);
+            cursor.write_u16(0, Endian::Big);
+            cursor.write_u16(0x4000, Endian::Big);
+            cursor.write_u8(64);
+            cursor.write_u8(IPPROTO_UDP);
+            cursor.write_u16(0, Endian::Big);
+            cursor.write(&src.octets());
+            cursor.write(&dst.octets());
+            let sum = checksum(&header, 0);
+            header[10..12].copy_from_slice(&sum.to_be_bytes());
+            packet.extend_from_slice(&header);
+        }
+        (src, dst) => {
+            let (src, dst) = (ipv6_of(src), ipv6_of(dst));
+            let sum = checksum(
+                &udp,
+                pseudo_sum(&src.octets(), &dst.octets(), udp_len as u32),
+            );
+            udp[6..8].copy_from_slice(&sum.to_be_bytes());
+
+            let mut cursor = Writer::new(&mut packet);
+            cursor.write_u32(0x6000_0000, Endian::Big);
+            cursor.write_u16(udp_len as u16, Endian::Big);
+            cursor.write_u8(IPPROTO_UDP);
+            cursor.write_u8(64);
+            cursor.write(&src.octets());
+            cursor.write(&dst.octets());
+        }
+    }
+    packet.extend_from_slice(&udp);
+    packet
+}
+
+#[derive(Clone)]
+pub struct CapturedDatagram {
+    pub time: Duration,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub payload: BytesMut,
+}
+
+pub struct PcapReader {
+    data: Vec<u8>,
+    pos: usize,
+    endian_big: bool,
+    nanos: bool,
+    linktype: u32,
+}
+
+impl PcapReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+    pub fn new<R: Read>(mut reader: R) -> Result<Self> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        let mut cursor = Reader::new(&data as &[u8]);
+        let magic = cursor.read_u32(Endian::Little)?;
+        let (endian_big, nanos) = match magic {
+            PCAP_MAGIC => (false, false),
+            PCAP_MAGIC_NANOS => (false, true),
+            _ if magic.swap_bytes() == PCAP_MAGIC => (true, false),
+            _ if magic.swap_bytes() == PCAP_MAGIC_NANOS => (true, true),
+            _ => return Err(std::io::Error::other("not a pcap file")),
+        };
+        cursor.next(16);
+        let linktype = if endian_big {
+            cursor.read_u32(Endian::Big)?
+        } else {
+            cursor.read_u32(Endian::Little)?
+        };
+        Ok(Self {
+            data,
+            pos: 24,
+            endian_big,
+            nanos,
+            linktype,
+        })
+    }
+
+    pub fn linktype(&self) -> u32 {
+        self.linktype
+    }
+
+    fn endian(&self) -> Endian {
+        if self.endian_big {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<(Duration, &[u8])>> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let mut cursor = Reader::new(&self.data[self.pos..]);
+        let seconds = cursor.read_u32(self.endian())?;
+        let fraction = cursor.read_u32(self.endian())?;
+        let incl_len = cursor.read_u32(self.endian())? as usize;
+        let _orig_len = cursor.read_u32(self.endian())?;
+        let start = self.pos + 16;
+        let end = start + incl_len;
+        if end > self.data.len() {
+            return Err(std::io::Error::other("truncated pcap record"));
+        }
+        self.pos = end;
+        let time = if self.nanos {
+            Duration::new(seconds as u64, fraction)
+        } else {
+            Duration::new(seconds as u64, fraction * 1000)
+        };
+        Ok(Some((time, &self.data[start..end])))
+    }
+
+    pub fn datagrams(mut self) -> Result<Vec<CapturedDatagram>> {
+        let linktype = self.linktype;
+        let mut ret = vec![];
+        while let Some((time, frame)) = self.next_record()? {
+            if let Some((source, destination, payload)) = udp_of(linktype, frame) {
+                ret.push(CapturedDatagram {
+                    time,
+                    source,
+                    destination,
+                    payload: BytesMut::from(payload),
+                });
+            }
+        }
+        Ok(ret)
+    }
+}
+
+fn udp_of(linktype: u32, frame: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let ip = match linktype {
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => frame,
+        LINKTYPE_NULL => frame.get(4..)?,
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            while u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]) == 0x8100 {
+                offset += 4;
+            }
+            frame.get(offset + 2..)?
+        }
+        LINKTYPE_LINUX_SLL => frame.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => frame.get(20..)?,
+        _ => return None,
+    };
+    let (source, destination, udp) = match ip.first()? >> 4 {
+        4 => {
+            let header_len = ((ip[0] & 0xf) as usize) * 4;
+            let fragment = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]);
+            if *ip.get(9)? != IPPROTO_UDP || fragment & 0x3fff != 0 {
+                return None;
+            }
+            let source: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                ip.get(header_len..)?,
+            )
+        }
+        6 => {
+            if *ip.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+            let source: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (
+                unmap(Ipv6Addr::from(source)),
+                unmap(Ipv6Addr::from(destination)),
+                ip.get(40..)?,
+            )
+        }
+        _ => return None,
+    };
+    let source_port = u16::from_be_bytes([*udp.first()?, *udp.get(1)?]);
+    let destination_port = u16::from_be_bytes([*udp.get(2)?, *udp.get(3)?]);
+    let length = u16::from_be_bytes([*udp.get(4)?, *udp.get(5)?]) as usize;
+    let payload = udp.get(8..length.max(8))?;
+    Some((
+        SocketAddr::new(source, source_port),
+        SocketAddr::new(destination, destination_port),
+        payload,
+    ))
+}
+
+fn unmap(address: Ipv6Addr) -> IpAddr {
+    match address.to_ipv4_mapped() {
+        Some(ip) => IpAddr::V4(ip),
+        None => IpAddr::V6(address),
+    }
+}
+
+fn same_endpoint(captured: SocketAddr, target: SocketAddr) -> bool {
+    captured.port() == target.port()
+        && (captured.ip() == target.ip() || captured.ip().is_unspecified())
+}
+
+pub struct Replay {
+    datagrams: Vec<CapturedDatagram>,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(PcapReader::open(path)?.datagrams()?))
+    }
+    pub fn new(datagrams: Vec<CapturedDatagram>) -> Self {
+        Self { datagrams }
+    }
+    pub fn datagrams(&self) -> &[CapturedDatagram] {
+        &self.datagrams
+    }
+
+    pub async fn run(self, network: MemoryNetwork, target: SocketAddr, paced: bool) -> usize {
+        let start = tokio::time::Instant::now();
+        let first = self.datagrams.first().map(|x| x.time).unwrap_or_default();
+        let mut count = 0;
+        for datagram in self.datagrams {
+            if !same_endpoint(datagram.destination, target) {
+                continue;
+            }
+            if paced {
+                let offset = datagram.time.checked_sub(first).unwrap_or_default();
+                tokio::time::delay_until(start + offset).await;
+            }
+            if network.inject(datagram.source, target, datagram.payload) {
+                count += 1;
+            }
+        }
+        count
+    }
+}
@@ -1,71 +1,273 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
 use actix::{dev::ToEnvelope, prelude::*};
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
+#[cfg(all(feature = "batch-io", target_os = "linux"))]
+use crate::batch::{BatchConfig, BatchUdpActor};
 use crate::{
-    macros::unwrap_or_return,
+    capture::{Capture, SetCapture},
+    endpoint::Routes,
+    macros::{debug, info, span, unwrap_or_return, Span},
     packets::*,
-    session::{time, ReceivedDatagram, Session, SessionEnd},
-    udp::{ReceivedUdp, SendUdp, UdpActor, UdpPacket},
+    send::{Coalescing, RateLimit, SendOptions},
+    session::{random, time, ReceivedDatagram, Session, SessionEnd},
+    transport::MemoryNetwork,
+    udp::{ReceivedUdp, SendUdp, Transport, UdpActor, UdpPacket},
     RAKNET_PROTOCOL_VERSION,
 };
 
+const MIGRATION_PROBE: Duration = Duration::from_secs(4);
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryReason {
+    Timeout,
+    AlreadyConnected,
+    DifferentVersion,
+    Disconnected,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    pub max_attempts: Option<u32>,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+    pub retry_on: Vec<RetryReason>,
+}
+
+impl ReconnectPolicy {
+    pub fn new(max_attempts: Option<u32>, initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_delay,
+            max_delay,
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_on: vec![RetryReason::Timeout, RetryReason::Disconnected],
+        }
+    }
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        assert!(multiplier >= 1.0, "backoff multiplier must be at least 1");
+        self.multiplier = multiplier;
+        self
+    }
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        assert!((0.0..=1.0).contains(&jitter), "jitter out of range");
+        self.jitter = jitter;
+        self
+    }
+    pub fn retry_on(mut self, reasons: Vec<RetryReason>) -> Self {
+        self.retry_on = reasons;
+        self
+    }
+
+    fn retries(&self, reason: RetryReason, attempt: u32) -> bool {
+        self.retry_on.contains(&reason) && self.max_attempts.is_none_or(|x| attempt < x)
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt as i32 - 1);
+        let backoff = backoff.min(self.max_delay.as_secs_f64());
+        let spread = random() as f64 / u64::MAX as f64;
+        Duration::from_secs_f64(backoff * (1.0 - self.jitter * spread))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new(None, Duration::from_millis(500), Duration::from_secs(30))
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 enum RakClientMsg {
-    Connect(SocketAddr),
+    CancelConnect,
     Packet(BytesMut),
+    PacketWith(Bytes, SendOptions),
     Disconnect,
+    DisconnectNow,
+    SetLinger(Option<Duration>),
+    SetCoalescing(Coalescing),
+    SetBandwidth(Option<RateLimit>),
+    SetMigration(bool),
+    SetReconnect(Option<ReconnectPolicy>),
+    Flush,
+    Capture(Option<Capture>),
+    SetNotices(Option<Recipient<RakClientNotice>>),
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(), ConnectError>")]
+struct Connect(SocketAddr);
+
+#[derive(Message)]
+#[rtype(result = "ClientState")]
+struct GetState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientState {
+    Idle,
+    Discovering,
+    Connecting,
+    Connected,
+    Closing,
+    Reconnecting,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectError {
+    Busy(ClientState),
+    AddressInUse,
+    Closed,
 }
 
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Busy(state) => write!(f, "client is busy ({:?})", state),
+            Self::AddressInUse => f.write_str("endpoint already has a session to this address"),
+            Self::Closed => f.write_str("client is closed"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
 pub struct ClientHandle {
     addr: Recipient<RakClientMsg>,
+    connect: Recipient<Connect>,
+    state: Recipient<GetState>,
 }
 
 impl ClientHandle {
     pub fn connect(&self, address: SocketAddr) {
-        unwrap_or_return!(self.addr.do_send(RakClientMsg::Connect(address)));
+        unwrap_or_return!(self.connect.do_send(Connect(address)));
+    }
+    pub async fn try_connect(&self, address: SocketAddr) -> Result<(), ConnectError> {
+        self.connect
+            .send(Connect(address))
+            .await
+            .unwrap_or(Err(ConnectError::Closed))
+    }
+    pub fn cancel_connect(&self) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::CancelConnect));
+    }
+    pub async fn state(&self) -> Option<ClientState> {
+        self.state.send(GetState).await.ok()
     }
     pub fn packet(&self, bytes: BytesMut) {
         unwrap_or_return!(self.addr.do_send(RakClientMsg::Packet(bytes)));
     }
+    pub fn packet_with(&self, bytes: Bytes, options: SendOptions) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::PacketWith(bytes, options)));
+    }
     pub fn disconnect(&self) {
         unwrap_or_return!(self.addr.do_send(RakClientMsg::Disconnect));
     }
+    pub fn disconnect_now(&self) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::DisconnectNow));
+    }
+    pub fn set_linger(&self, linger: Option<Duration>) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetLinger(linger)));
+    }
+    pub fn set_coalescing(&self, coalescing: Coalescing) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetCoalescing(coalescing)));
+    }
+    pub fn set_bandwidth(&self, limit: Option<RateLimit>) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetBandwidth(limit)));
+    }
+    pub fn set_migration(&self, migration: bool) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetMigration(migration)));
+    }
+    pub fn set_reconnect(&self, policy: Option<ReconnectPolicy>) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetReconnect(policy)));
+    }
+    pub fn flush(&self) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::Flush));
+    }
+    pub fn capture(&self, capture: Option<Capture>) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::Capture(capture)));
+    }
+    pub fn set_notices(&self, notices: Option<Recipient<RakClientNotice>>) {
+        unwrap_or_return!(self.addr.do_send(RakClientMsg::SetNotices(notices)));
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ConnectionFailedReason {
     AlreadyConnected,
     DifferentVersion,
     Timeout,
 }
+
+impl From<ConnectionFailedReason> for RetryReason {
+    fn from(reason: ConnectionFailedReason) -> Self {
+        match reason {
+            ConnectionFailedReason::AlreadyConnected => RetryReason::AlreadyConnected,
+            ConnectionFailedReason::DifferentVersion => RetryReason::DifferentVersion,
+            ConnectionFailedReason::Timeout => RetryReason::Timeout,
+        }
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub enum RakClientEvent {
     ConnectionFailed(ConnectionFailedReason),
     Connected,
-    Packet(BytesMut),
+    Packet(Bytes),
     Disconnected,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub enum RakClientNotice {
+    Reconnecting(u32),
+    Reconnected,
+    StateChanged(ClientState),
+}
+
 pub struct RakClient<T>
 where
     T: Actor,
     T: Handler<RakClientEvent>,
     <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
 {
-    udp: Addr<UdpActor<Self>>,
+    udp: Transport,
     guid: u64,
+    state: ClientState,
     mediator: Option<Addr<ClientMediator>>,
     handler: Addr<T>,
+    notices: Option<Recipient<RakClientNotice>>,
     session: Option<Session<Self>>,
+    linger: Option<Duration>,
+    coalescing: Coalescing,
+    bandwidth: Option<RateLimit>,
+    migration: bool,
+    cookie: Option<u64>,
+    last_probe: Option<Instant>,
+    reconnect: Option<ReconnectPolicy>,
+    attempt: u32,
+    target: Option<SocketAddr>,
+    closing: bool,
+    reconnect_handle: Option<SpawnHandle>,
 
     tick_handle: Option<SpawnHandle>,
     remote: Option<SocketAddr>,
     disconnect_handle: Option<SpawnHandle>,
 
-    udp_worker: Arbiter,
+    routes: Option<Routes>,
+    udp_worker: Option<Arbiter>,
+    span: Span,
 }
 
 impl<T> RakClient<T>
@@ -80,20 +282,108 @@ where
         handler: Addr<T>,
         arbiter: &Arbiter,
     ) -> ClientHandle {
-        let udp_worker = Arbiter::new();
-        let addr = Self::start_in_arbiter(arbiter, move |ctx| Self {
-            udp: UdpActor::new(socket, ctx.address(), &udp_worker),
+        Self::start(guid, handler, arbiter, move |addr, udp_worker| {
+            Transport::new(UdpActor::new(socket, addr, udp_worker, None))
+        })
+    }
+
+    #[cfg(all(feature = "batch-io", target_os = "linux"))]
+    pub fn init_batched(
+        socket: std::net::UdpSocket,
+        guid: u64,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        config: BatchConfig,
+    ) -> std::io::Result<ClientHandle> {
+        socket.set_nonblocking(true)?;
+        Ok(Self::start(
             guid,
-            mediator: None,
             handler,
-            session: None,
-            tick_handle: None,
-            remote: None,
-            disconnect_handle: None,
-            udp_worker,
+            arbiter,
+            move |addr, udp_worker| {
+                Transport::new(BatchUdpActor::new(socket, config, addr, udp_worker, None))
+            },
+        ))
+    }
+
+    pub fn init_in_memory(
+        network: &MemoryNetwork,
+        local: SocketAddr,
+        guid: u64,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+    ) -> ClientHandle {
+        let network = network.clone();
+        Self::start(guid, handler, arbiter, move |addr, udp_worker| {
+            Transport::new(network.bind(local, addr.recipient(), udp_worker, None))
+        })
+    }
+
+    pub(crate) fn on_endpoint(
+        guid: u64,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        udp: Transport,
+        routes: Routes,
+    ) -> ClientHandle {
+        Self::spawn(guid, handler, arbiter, Some(routes), move |_addr| {
+            (udp, None)
+        })
+    }
+
+    fn start<F>(guid: u64, handler: Addr<T>, arbiter: &Arbiter, transport: F) -> ClientHandle
+    where
+        F: FnOnce(Addr<Self>, &Arbiter) -> Transport + Send + 'static,
+    {
+        let udp_worker = Arbiter::new();
+        Self::spawn(guid, handler, arbiter, None, move |addr| {
+            (transport(addr, &udp_worker), Some(udp_worker))
+        })
+    }
+
+    fn spawn<F>(
+        guid: u64,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        routes: Option<Routes>,
+        transport: F,
+    ) -> ClientHandle
+    where
+        F: FnOnce(Addr<Self>) -> (Transport, Option<Arbiter>) + Send + 'static,
+    {
+        let addr = Self::start_in_arbiter(arbiter, move |ctx| {
+            let (udp, udp_worker) = transport(ctx.address());
+            Self {
+                udp,
+                guid,
+                state: ClientState::Idle,
+                mediator: None,
+                handler,
+                notices: None,
+                session: None,
+                linger: None,
+                coalescing: Coalescing::default(),
+                bandwidth: None,
+                migration: false,
+                cookie: None,
+                last_probe: None,
+                reconnect: None,
+                attempt: 0,
+                target: None,
+                closing: false,
+                reconnect_handle: None,
+                tick_handle: None,
+                remote: None,
+                disconnect_handle: None,
+                routes,
+                udp_worker,
+                span: span!("client", guid),
+            }
         });
         ClientHandle {
-            addr: addr.recipient::<RakClientMsg>(),
+            addr: addr.clone().recipient::<RakClientMsg>(),
+            connect: addr.clone().recipient::<Connect>(),
+            state: addr.recipient::<GetState>(),
         }
     }
 }
@@ -105,16 +395,152 @@ where
     <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
 {
     fn update(&mut self, ctx: &mut Context<Self>) {
-        if self.session.is_some() {
-            self.session.as_mut().unwrap().update();
+        if let Some(session) = &mut self.session {
+            session.update();
+            if let (Some(cookie), None) = (self.cookie, self.disconnect_handle) {
+                let probed = self.last_probe.map(|x| x.elapsed());
+                if session.idle() > MIGRATION_PROBE && probed.is_none_or(|x| x > PROBE_INTERVAL) {
+                    debug!("probing for address migration");
+                    self.last_probe = Some(Instant::now());
+                    let remote = self.remote.unwrap();
+                    let request2 = OpenConnectionRequest2::new(remote, session.mtu(), self.guid)
+                        .with_cookie(cookie);
+                    self.udp.send_to(encode(request2), remote);
+                }
+            }
         } else {
             ctx.cancel_future(self.tick_handle.unwrap());
         }
     }
-    fn connection_timeout(&mut self) {
-        self.handler.do_send(RakClientEvent::ConnectionFailed(
-            ConnectionFailedReason::Timeout,
+    fn connection_timeout(&mut self, ctx: &mut Context<Self>) {
+        info!("connection request timed out");
+        self.disconnect_handle = None;
+        self.failed(ConnectionFailedReason::Timeout, ctx);
+    }
+
+    fn connect(&mut self, addr: SocketAddr, ctx: &mut Context<Self>) -> Result<(), ConnectError> {
+        if let Some(routes) = &self.routes {
+            if !routes.claim(addr, ctx.address().recipient()) {
+                return Err(ConnectError::AddressInUse);
+            }
+        }
+        self.span = span!("client", guid = self.guid, remote = %addr);
+        let _enter = self.span.clone().entered();
+        info!("connecting");
+        let udp = self.udp.send.clone();
+        self.remote = Some(addr);
+        self.set_state(ClientState::Discovering);
+        self.mediator = Some(ClientMediator::new(
+            udp,
+            ctx.address().recipient::<MediatorEvent>(),
+            self.guid,
+            addr,
+            self.span.clone(),
         ));
+        Ok(())
+    }
+
+    fn notice(&self, notice: RakClientNotice) {
+        if let Some(notices) = &self.notices {
+            let _ = notices.do_send(notice);
+        }
+    }
+
+    fn set_state(&mut self, state: ClientState) {
+        if self.state != state {
+            debug!(?state, "state changed");
+            self.state = state;
+            self.notice(RakClientNotice::StateChanged(state));
+        }
+    }
+
+    fn reset(&mut self, ctx: &mut Context<Self>) {
+        if let Some(mediator) = self.mediator.take() {
+            mediator.do_send(TerminateMediator);
+        }
+        for handle in [self.disconnect_handle.take(), self.tick_handle.take()]
+            .into_iter()
+            .flatten()
+        {
+            ctx.cancel_future(handle);
+        }
+        self.session = None;
+        self.release();
+    }
+
+    fn release(&mut self) {
+        if let (Some(routes), Some(remote)) = (&self.routes, self.remote.take()) {
+            routes.release(remote);
+        }
+    }
+
+    fn cancel(&mut self, ctx: &mut Context<Self>) {
+        self.stop_reconnecting(ctx);
+        match self.state {
+            ClientState::Discovering | ClientState::Reconnecting => {
+                info!("connection attempt cancelled");
+                self.reset(ctx);
+                self.set_state(ClientState::Idle);
+            }
+            ClientState::Connecting => {
+                info!("connection attempt cancelled");
+                if let Some(handle) = self.disconnect_handle.take() {
+                    ctx.cancel_future(handle);
+                }
+                if let Some(session) = &mut self.session {
+                    session.disconnect_now();
+                }
+                self.set_state(ClientState::Closing);
+            }
+            _ => {}
+        }
+    }
+
+    fn stop_reconnecting(&mut self, ctx: &mut Context<Self>) {
+        self.closing = true;
+        self.attempt = 0;
+        if let Some(handle) = self.reconnect_handle.take() {
+            ctx.cancel_future(handle);
+        }
+    }
+
+    fn failed(&mut self, reason: ConnectionFailedReason, ctx: &mut Context<Self>) {
+        if !self.retry(reason.into(), ctx) {
+            self.attempt = 0;
+            self.reset(ctx);
+            self.set_state(ClientState::Idle);
+            self.handler
+                .do_send(RakClientEvent::ConnectionFailed(reason));
+        }
+    }
+
+    fn retry(&mut self, reason: RetryReason, ctx: &mut Context<Self>) -> bool {
+        let (policy, target) = match (&self.reconnect, self.target) {
+            (Some(policy), Some(target)) if !self.closing => (policy, target),
+            _ => return false,
+        };
+        if !policy.retries(reason, self.attempt) {
+            info!(attempts = self.attempt, "giving up reconnecting");
+            return false;
+        }
+        self.attempt += 1;
+        let delay = policy.delay(self.attempt);
+        info!(attempt = self.attempt, ?delay, ?reason, "reconnecting");
+        self.reset(ctx);
+        self.set_state(ClientState::Reconnecting);
+        self.notice(RakClientNotice::Reconnecting(self.attempt));
+        self.reconnect_handle = Some(ctx.run_later(delay, move |me, ctx| {
+            me.reconnect_handle = None;
+            if me.connect(target, ctx).is_err() {
+                info!("reconnect abandoned: address in use");
+                me.attempt = 0;
+                me.set_state(ClientState::Idle);
+                me.handler.do_send(RakClientEvent::ConnectionFailed(
+                    ConnectionFailedReason::AlreadyConnected,
+                ));
+            }
+        }));
+        true
     }
 }
 
@@ -126,7 +552,10 @@ where
 {
     type Context = Context<Self>;
     fn stopped(&mut self, _ctx: &mut Self::Context) {
-        self.udp_worker.stop();
+        self.release();
+        if let Some(udp_worker) = &self.udp_worker {
+            udp_worker.stop();
+        }
     }
 }
 
@@ -138,6 +567,7 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: ReceivedUdp, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
         if self.remote.is_none() {
             return;
         }
@@ -148,8 +578,14 @@ where
 
         if let Some(mediator) = &self.mediator {
             mediator.do_send(msg);
-        } else if self.session.is_some() {
-            self.session.as_mut().unwrap().handle(msg);
+        } else if let Some(session) = &mut self.session {
+            if msg.0.bytes[0] == OpenConnectionReply2::ID {
+                if self.last_probe.take().is_some() {
+                    info!("address migration confirmed");
+                }
+                return;
+            }
+            session.handle(msg);
         }
     }
 }
@@ -162,28 +598,115 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: RakClientMsg, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
         match msg {
-            RakClientMsg::Connect(addr) => {
-                let udp = self.udp.clone().recipient::<SendUdp>();
-                self.remote = Some(addr);
-                self.mediator = Some(ClientMediator::new(
-                    udp,
-                    ctx.address().recipient::<MediatorEvent>(),
-                    self.guid,
-                    addr,
-                ))
-            }
+            RakClientMsg::CancelConnect => self.cancel(ctx),
             RakClientMsg::Packet(bytes) => {
-                if self.session.is_some() {
-                    self.session.as_mut().unwrap().send_to(bytes);
+                if let Some(session) = &mut self.session {
+                    session.send_to(bytes.freeze());
+                }
+            }
+            RakClientMsg::PacketWith(bytes, options) => {
+                if let Some(session) = &mut self.session {
+                    session.send_with(bytes, &options);
                 }
             }
             RakClientMsg::Disconnect => {
-                if self.session.is_some() {
-                    self.session.as_mut().unwrap().disconnect();
+                if self.state != ClientState::Connected {
+                    self.cancel(ctx);
+                    return;
+                }
+                self.stop_reconnecting(ctx);
+                if let Some(session) = &mut self.session {
+                    session.disconnect();
+                }
+                self.set_state(ClientState::Closing);
+            }
+            RakClientMsg::DisconnectNow => {
+                if self.state != ClientState::Connected {
+                    self.cancel(ctx);
+                    return;
+                }
+                self.stop_reconnecting(ctx);
+                if let Some(session) = &mut self.session {
+                    session.disconnect_now();
+                }
+                self.set_state(ClientState::Closing);
+            }
+            RakClientMsg::SetLinger(linger) => {
+                self.linger = linger;
+                if let Some(session) = &mut self.session {
+                    session.set_linger(linger);
+                }
+            }
+            RakClientMsg::SetCoalescing(coalescing) => {
+                self.coalescing = coalescing;
+                if let Some(session) = &mut self.session {
+                    session.set_coalescing(coalescing);
                 }
             }
+            RakClientMsg::SetBandwidth(limit) => {
+                self.bandwidth = limit;
+                if let Some(session) = &mut self.session {
+                    session.set_bandwidth(limit);
+                }
+            }
+            RakClientMsg::SetMigration(migration) => {
+                self.migration = migration;
+            }
+            RakClientMsg::SetReconnect(policy) => {
+                self.reconnect = policy;
+            }
+            RakClientMsg::Flush => {
+                if let Some(session) = &mut self.session {
+                    session.flush();
+                }
+            }
+            RakClientMsg::Capture(capture) => {
+                unwrap_or_return!(self.udp.capture.do_send(SetCapture(capture)));
+            }
+            RakClientMsg::SetNotices(notices) => {
+                self.notices = notices;
+            }
+        }
+    }
+}
+
+impl<T> Handler<Connect> for RakClient<T>
+where
+    T: Actor,
+    T: Handler<RakClientEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
+{
+    type Result = Result<(), ConnectError>;
+    fn handle(&mut self, msg: Connect, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        match self.state {
+            ClientState::Idle | ClientState::Reconnecting => {}
+            state => {
+                info!(?state, "connect refused: client is busy");
+                return Err(ConnectError::Busy(state));
+            }
+        }
+        if let Some(handle) = self.reconnect_handle.take() {
+            ctx.cancel_future(handle);
         }
+        self.target = Some(msg.0);
+        self.attempt = 0;
+        self.closing = false;
+        self.connect(msg.0, ctx)
+    }
+}
+
+impl<T> Handler<GetState> for RakClient<T>
+where
+    T: Actor,
+    T: Handler<RakClientEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
+{
+    type Result = MessageResult<GetState>;
+    fn handle(&mut self, _msg: GetState, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.state)
     }
 }
 
@@ -195,48 +718,57 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: MediatorEvent, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(mediator) = &self.mediator {
+        let _enter = self.span.clone().entered();
+        if let Some(mediator) = self.mediator.take() {
             match msg {
                 MediatorEvent::AlreadyConnected => {
-                    self.handler.do_send(RakClientEvent::ConnectionFailed(
-                        ConnectionFailedReason::AlreadyConnected,
-                    ));
+                    info!("connection refused: already connected");
+                    self.failed(ConnectionFailedReason::AlreadyConnected, ctx);
                 }
                 MediatorEvent::DifferentVersion => {
-                    self.handler.do_send(RakClientEvent::ConnectionFailed(
-                        ConnectionFailedReason::DifferentVersion,
-                    ));
+                    info!("connection refused: incompatible protocol");
+                    self.failed(ConnectionFailedReason::DifferentVersion, ctx);
                 }
                 MediatorEvent::Success(mtu) => {
+                    debug!(mtu, "offline handshake finished");
                     let mut session = Session::new(
                         self.remote.unwrap(),
                         mtu,
-                        self.udp.clone().recipient::<SendUdp>(),
+                        self.udp.send.clone(),
                         ctx.address(),
+                        None,
                     );
-                    let request = ConnectionRequest::new(
+                    session.set_linger(self.linger);
+                    session.set_coalescing(self.coalescing);
+                    session.set_bandwidth(self.bandwidth);
+                    let mut request = ConnectionRequest::new(
                         self.guid,
                         time().try_into().unwrap_or_default(),
                         false,
                     );
+                    self.cookie = self.migration.then(random);
+                    self.last_probe = None;
+                    if let Some(cookie) = self.cookie {
+                        request = request.with_cookie(cookie);
+                    }
                     session.send_system_packet(request, Reliability::Reliable);
                     self.session = Some(session);
-                    self.disconnect_handle = Some(
-                        ctx.run_later(Duration::from_secs(5), |me, _ctx| me.connection_timeout()),
-                    );
+                    self.set_state(ClientState::Connecting);
+                    self.disconnect_handle =
+                        Some(ctx.run_later(Duration::from_secs(5), |me, ctx| {
+                            me.connection_timeout(ctx)
+                        }));
                     self.tick_handle =
                         Some(ctx.run_interval(Duration::from_millis(10), |me, ctx| {
                             me.update(ctx);
                         }));
                 }
                 MediatorEvent::Timeout => {
-                    self.handler.do_send(RakClientEvent::ConnectionFailed(
-                        ConnectionFailedReason::Timeout,
-                    ));
+                    info!("offline handshake timed out");
+                    self.failed(ConnectionFailedReason::Timeout, ctx);
                 }
             }
             mediator.do_send(TerminateMediator);
-            self.mediator = None;
         }
     }
 }
@@ -249,6 +781,7 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: ReceivedDatagram, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
         match msg.0.data[0] {
             ConnectionRequestAccepted::ID => {
                 if let Some(handle) = self.disconnect_handle {
@@ -265,7 +798,13 @@ where
                         .as_mut()
                         .unwrap()
                         .send_system_packet(connected, Reliability::ReliableOrdered);
-                    self.session.as_mut().unwrap().force_flush();
+                    self.session.as_mut().unwrap().flush();
+                    info!("connected");
+                    self.set_state(ClientState::Connected);
+                    if self.attempt > 0 {
+                        self.attempt = 0;
+                        self.notice(RakClientNotice::Reconnected);
+                    }
                     self.handler.do_send(RakClientEvent::Connected);
                 }
             }
@@ -283,10 +822,12 @@ where
     <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
 {
     type Result = ();
-    fn handle(&mut self, _msg: SessionEnd, _ctx: &mut Self::Context) -> Self::Result {
-        self.remote = None;
-        self.session = None;
+    fn handle(&mut self, _msg: SessionEnd, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        self.reset(ctx);
+        self.set_state(ClientState::Idle);
         self.handler.do_send(RakClientEvent::Disconnected);
+        self.retry(RetryReason::Disconnected, ctx);
     }
 }
 
@@ -300,6 +841,7 @@ pub(crate) struct ClientMediator {
 
     request1_count: u32,
     next_request1_handle: Option<SpawnHandle>,
+    span: Span,
 }
 
 impl ClientMediator {
@@ -308,6 +850,7 @@ impl ClientMediator {
         parent: Recipient<MediatorEvent>,
         guid: u64,
         address: SocketAddr,
+        span: Span,
     ) -> Addr<Self> {
         Self::create(|_ctx| Self {
             udp,
@@ -317,6 +860,7 @@ impl ClientMediator {
             disconnect_timer: None,
             request1_count: 0,
             next_request1_handle: None,
+            span,
         })
     }
     fn request1(&mut self, ctx: &mut Context<Self>) {
@@ -332,24 +876,34 @@ impl ClientMediator {
             return;
         }
         self.request1_count += 1;
+        debug!(
+            attempt = self.request1_count,
+            mtu = mtu_size,
+            "open connection request 1"
+        );
 
         let request1 = OpenConnectionRequest1::new(RAKNET_PROTOCOL_VERSION, mtu_size);
 
         unwrap_or_return!(self.udp.do_send(SendUdp(UdpPacket {
-            bytes: encode(request1),
+            bytes: encode(request1).freeze(),
             addr: self.address,
         })));
-        self.next_request1_handle =
-            Some(ctx.run_later(Duration::from_millis(510), |me, ctx| me.request1(ctx)));
+        self.next_request1_handle = Some(ctx.run_later(Duration::from_millis(510), |me, ctx| {
+            let _enter = me.span.clone().entered();
+            me.request1(ctx)
+        }));
     }
     fn request2(&mut self, mtu: u16, ctx: &mut Context<Self>) {
+        debug!(mtu, "open connection request 2");
         let request2 = OpenConnectionRequest2::new(self.address, mtu, self.guid);
         unwrap_or_return!(self.udp.do_send(SendUdp(UdpPacket {
-            bytes: encode(request2),
+            bytes: encode(request2).freeze(),
             addr: self.address,
         })));
-        self.next_request1_handle =
-            Some(ctx.run_later(Duration::from_millis(510), |me, ctx| me.request1(ctx)));
+        self.next_request1_handle = Some(ctx.run_later(Duration::from_millis(510), |me, ctx| {
+            let _enter = me.span.clone().entered();
+            me.request1(ctx)
+        }));
     }
     fn success(&mut self, mtu: u16, ctx: &mut Context<Self>) {
         if let Some(handle) = self.disconnect_timer {
@@ -380,6 +934,7 @@ impl ClientMediator {
 impl Actor for ClientMediator {
     type Context = Context<Self>;
     fn started(&mut self, ctx: &mut Self::Context) {
+        let _enter = self.span.clone().entered();
         self.disconnect_timer = Some(ctx.run_later(Duration::from_secs(10), |me, ctx| {
             me.timeout(ctx);
         }));
@@ -390,6 +945,7 @@ impl Actor for ClientMediator {
 impl Handler<ReceivedUdp> for ClientMediator {
     type Result = ();
     fn handle(&mut self, msg: ReceivedUdp, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
         match msg.0.bytes[0] {
             OpenConnectionReply1::ID => {
                 if let Some(handle) = self.next_request1_handle {
@@ -0,0 +1,242 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use actix::{dev::ToEnvelope, prelude::*};
+
+use crate::{
+    capture::{Capture, SetCapture},
+    client::{ClientHandle, RakClient, RakClientEvent},
+    macros::{info, unwrap_or_return},
+    packets::{
+        decode, encode, AlreadyConnected, NatPunch, OpenConnectionRequest1, OpenConnectionRequest2,
+        Packet, UnconnectedPing,
+    },
+    relay, server,
+    transport::MemoryNetwork,
+    udp::{ReceivedUdp, SendUdp, Transport, UdpActor, UdpPacket},
+};
+
+#[derive(Clone, Default)]
+pub(crate) struct Routes {
+    outgoing: Arc<RwLock<HashMap<SocketAddr, Recipient<ReceivedUdp>>>>,
+    incoming: Option<server::Routes>,
+}
+
+impl Routes {
+    pub(crate) fn claim(&self, address: SocketAddr, client: Recipient<ReceivedUdp>) -> bool {
+        if let Some(incoming) = &self.incoming {
+            if incoming.read().unwrap().contains_key(&address) {
+                return false;
+            }
+        }
+        match self.outgoing.write().unwrap().entry(address) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(client);
+                true
+            }
+        }
+    }
+    pub(crate) fn release(&self, address: SocketAddr) {
+        self.outgoing.write().unwrap().remove(&address);
+    }
+    fn get(&self, address: &SocketAddr) -> Option<Recipient<ReceivedUdp>> {
+        self.outgoing.read().unwrap().get(address).cloned()
+    }
+    fn len(&self) -> usize {
+        self.outgoing.read().unwrap().len()
+    }
+}
+
+pub(crate) struct Inbound {
+    pub server: Recipient<ReceivedUdp>,
+    pub routes: server::Routes,
+    pub guid: u64,
+}
+
+pub struct ClientEndpoint {
+    udp: Transport,
+    routes: Routes,
+    server: Option<(Recipient<ReceivedUdp>, u64)>,
+    punch: Option<Recipient<ReceivedUdp>>,
+    relay: Option<Recipient<SendUdp>>,
+    udp_worker: Arbiter,
+}
+
+impl ClientEndpoint {
+    pub fn bind(socket: tokio::net::UdpSocket, arbiter: &Arbiter) -> EndpointHandle {
+        Self::start(arbiter, None, move |addr, udp_worker| {
+            Transport::new(UdpActor::new(socket, addr, udp_worker, None))
+        })
+    }
+
+    pub fn bind_in_memory(
+        network: &MemoryNetwork,
+        local: SocketAddr,
+        arbiter: &Arbiter,
+    ) -> EndpointHandle {
+        let network = network.clone();
+        Self::start(arbiter, None, move |addr, udp_worker| {
+            Transport::new(network.bind(local, addr.recipient(), udp_worker, None))
+        })
+    }
+
+    pub(crate) fn start<F>(
+        arbiter: &Arbiter,
+        inbound: Option<Inbound>,
+        transport: F,
+    ) -> EndpointHandle
+    where
+        F: FnOnce(Addr<Self>, &Arbiter) -> Transport + Send + 'static,
+    {
+        let (server, incoming) = match inbound {
+            Some(inbound) => (Some((inbound.server, inbound.guid)), Some(inbound.routes)),
+            None => (None, None),
+        };
+        let routes = Routes {
+            outgoing: Default::default(),
+            incoming,
+        };
+        let shared = routes.clone();
+        let udp_worker = Arbiter::new();
+        let addr = Self::start_in_arbiter(arbiter, move |ctx| Self {
+            udp: transport(ctx.address(), &udp_worker),
+            routes: shared,
+            server,
+            punch: None,
+            relay: None,
+            udp_worker,
+        });
+        EndpointHandle { addr, routes }
+    }
+}
+
+impl Actor for ClientEndpoint {
+    type Context = Context<Self>;
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.udp_worker.stop();
+    }
+}
+
+impl Handler<ReceivedUdp> for ClientEndpoint {
+    type Result = ();
+    fn handle(&mut self, msg: ReceivedUdp, _ctx: &mut Self::Context) -> Self::Result {
+        let id = msg.0.bytes.first().copied();
+        if id == Some(NatPunch::ID) {
+            if let Some(punch) = &self.punch {
+                unwrap_or_return!(punch.do_send(msg));
+            }
+            return;
+        }
+        let handshake = matches!(
+            id,
+            Some(OpenConnectionRequest1::ID) | Some(OpenConnectionRequest2::ID)
+        );
+        let request = handshake || id == Some(UnconnectedPing::ID);
+        match (self.routes.get(&msg.0.addr), &self.server) {
+            (Some(client), _) if !request => unwrap_or_return!(client.do_send(msg)),
+            (Some(client), Some((server, guid))) if id == Some(OpenConnectionRequest2::ID) => {
+                let request2 = unwrap_or_return!(decode::<OpenConnectionRequest2>(&msg.0.bytes));
+                if request2.guid <= *guid {
+                    return;
+                }
+                let addr = msg.0.addr;
+                info!(%addr, "yielding outgoing session to incoming handshake");
+                self.routes.release(addr);
+                let _ = client.do_send(ReceivedUdp(UdpPacket {
+                    bytes: encode(AlreadyConnected::new(*guid)).freeze(),
+                    addr,
+                }));
+                unwrap_or_return!(server.do_send(msg));
+            }
+            (_, Some((server, _))) => unwrap_or_return!(server.do_send(msg)),
+            _ => {}
+        }
+    }
+}
+
+impl Handler<SendUdp> for ClientEndpoint {
+    type Result = ();
+    fn handle(&mut self, msg: SendUdp, _ctx: &mut Self::Context) -> Self::Result {
+        if relay::relay_guid(&msg.0.addr).is_some() {
+            if let Some(relay) = &self.relay {
+                unwrap_or_return!(relay.do_send(msg));
+            }
+            return;
+        }
+        unwrap_or_return!(self.udp.send.do_send(msg));
+    }
+}
+
+impl Handler<SetPunch> for ClientEndpoint {
+    type Result = ();
+    fn handle(&mut self, msg: SetPunch, _ctx: &mut Self::Context) -> Self::Result {
+        self.punch = msg.0;
+    }
+}
+
+impl Handler<SetRelay> for ClientEndpoint {
+    type Result = ();
+    fn handle(&mut self, msg: SetRelay, _ctx: &mut Self::Context) -> Self::Result {
+        self.relay = msg.0;
+    }
+}
+
+impl Handler<SetCapture> for ClientEndpoint {
+    type Result = ();
+    fn handle(&mut self, msg: SetCapture, _ctx: &mut Self::Context) -> Self::Result {
+        unwrap_or_return!(self.udp.capture.do_send(msg));
+    }
+}
+
+#[derive(Clone)]
+pub struct EndpointHandle {
+    addr: Addr<ClientEndpoint>,
+    routes: Routes,
+}
+
+impl EndpointHandle {
+    pub fn client<T>(&self, guid: u64, handler: Addr<T>, arbiter: &Arbiter) -> ClientHandle
+    where
+        T: Actor,
+        T: Handler<RakClientEvent>,
+        <T as actix::Actor>::Context: ToEnvelope<T, RakClientEvent>,
+    {
+        RakClient::on_endpoint(
+            guid,
+            handler,
+            arbiter,
+            self.transport(),
+            self.routes.clone(),
+        )
+    }
+    pub(crate) fn transport(&self) -> Transport {
+        Transport::new(self.addr.clone())
+    }
+    pub(crate) fn set_punch(&self, punch: Option<Recipient<ReceivedUdp>>) {
+        self.addr.do_send(SetPunch(punch));
+    }
+    pub(crate) fn set_relay(&self, relay: Option<Recipient<SendUdp>>) {
+        self.addr.do_send(SetRelay(relay));
+    }
+    pub(crate) fn received(&self, packet: UdpPacket) {
+        self.addr.do_send(ReceivedUdp(packet));
+    }
+    pub fn connections(&self) -> usize {
+        self.routes.len()
+    }
+    pub fn capture(&self, capture: Option<Capture>) {
+        self.addr.do_send(SetCapture(capture));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct SetPunch(Option<Recipient<ReceivedUdp>>);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct SetRelay(Option<Recipient<SendUdp>>);
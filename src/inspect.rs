@@ -0,0 +1,275 @@
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+};
+
+use crate::{
+    packets::*,
+    reader::{Endian, Reader},
+};
+
+const ACK_FLAG: u8 = 0x40;
+const NACK_FLAG: u8 = 0x20;
+const DATAGRAM_FLAG: u8 = 0x80;
+
+#[derive(Clone, Debug)]
+pub enum Dissection {
+    FrameSet {
+        header: u8,
+        sequence_number: u32,
+        frames: Vec<FrameDissection>,
+    },
+    Ack(Vec<(u32, u32)>),
+    Nack(Vec<(u32, u32)>),
+    Offline(Message),
+    Malformed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameDissection {
+    pub frame: Frame,
+    pub message: Message,
+}
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    UnconnectedPing(UnconnectedPing),
+    UnconnectedPong(UnconnectedPong),
+    OpenConnectionRequest1(OpenConnectionRequest1),
+    OpenConnectionReply1(OpenConnectionReply1),
+    OpenConnectionRequest2(OpenConnectionRequest2),
+    OpenConnectionReply2(OpenConnectionReply2),
+    AlreadyConnected(AlreadyConnected),
+    IncompatibleProtocolVersion(IncompatibleProtocolVersion),
+    ConnectedPing(ConnectedPing),
+    ConnectedPong(ConnectedPong),
+    ConnectionRequest(ConnectionRequest),
+    ConnectionRequestAccepted(ConnectionRequestAccepted),
+    NewIncomingConnection(NewIncomingConnection),
+    Disconnected(Disconnected),
+    NatPunchthroughRequest(NatPunchthroughRequest),
+    NatConnectAt(NatConnectAt),
+    NatPunchthroughFailed(NatPunchthroughFailed),
+    NatPunch(NatPunch),
+    RelayRequest(RelayRequest),
+    RelayReady(RelayReady),
+    RelayData(RelayData),
+    RelayClosed(RelayClosed),
+    Fragment { length: usize },
+    User { id: u8, length: usize },
+    Unknown { id: u8, length: usize },
+    Malformed { id: u8, error: String },
+}
+
+pub fn dissect(datagram: &[u8]) -> Dissection {
+    let id = match datagram.first() {
+        Some(id) => *id,
+        None => return Dissection::Malformed("empty datagram".to_owned()),
+    };
+    if id & DATAGRAM_FLAG == 0 {
+        Dissection::Offline(offline(datagram))
+    } else if id & ACK_FLAG != 0 {
+        match ranges(&datagram[1..]) {
+            Ok(ranges) => Dissection::Ack(ranges),
+            Err(e) => Dissection::Malformed(format!("ACK: {}", e)),
+        }
+    } else if id & NACK_FLAG != 0 {
+        match ranges(&datagram[1..]) {
+            Ok(ranges) => Dissection::Nack(ranges),
+            Err(e) => Dissection::Malformed(format!("NACK: {}", e)),
+        }
+    } else {
+        match FrameSet::decode(datagram) {
+            Ok(frame_set) => Dissection::FrameSet {
+                header: frame_set.header,
+                sequence_number: frame_set.sequence_number,
+                frames: frame_set
+                    .datas
+                    .into_iter()
+                    .map(|frame| FrameDissection {
+                        message: if frame.split {
+                            Message::Fragment {
+                                length: frame.data.len(),
+                            }
+                        } else {
+                            connected(&frame.data)
+                        },
+                        frame,
+                    })
+                    .collect(),
+            },
+            Err(e) => Dissection::Malformed(format!("FrameSet: {}", e)),
+        }
+    }
+}
+
+pub fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let digits = text
+        .split(|c: char| c.is_whitespace() || c == ':' || c == ',')
+        .map(|x| x.trim_start_matches("0x"))
+        .collect::<String>();
+    if digits.len() % 2 != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "odd number of hex digits",
+        ));
+    }
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid hex digit"))
+        })
+        .collect()
+}
+
+fn ranges(payload: &[u8]) -> Result<Vec<(u32, u32)>> {
+    let mut cursor = Reader::new(payload);
+    let record_count = cursor.read_u16(Endian::Big)?;
+    let mut ranges = Vec::with_capacity(record_count as usize);
+    for _ in 0..record_count {
+        let max_equals_min = cursor.read_u8()? != 0;
+        let min = cursor.read_u24(Endian::Little)?;
+        if max_equals_min {
+            ranges.push((min, min));
+        } else {
+            ranges.push((min, cursor.read_u24(Endian::Little)?));
+        }
+    }
+    Ok(ranges)
+}
+
+fn read<T: Packet>(buf: &[u8], f: fn(T) -> Message) -> Message {
+    match T::read(&buf[1..]) {
+        Ok(packet) => f(packet),
+        Err(e) => Message::Malformed {
+            id: buf[0],
+            error: e.to_string(),
+        },
+    }
+}
+
+fn offline(buf: &[u8]) -> Message {
+    match buf[0] {
+        0x01 | 0x02 => read(buf, Message::UnconnectedPing),
+        UnconnectedPong::ID => read(buf, Message::UnconnectedPong),
+        OpenConnectionRequest1::ID => read(buf, Message::OpenConnectionRequest1),
+        OpenConnectionReply1::ID => read(buf, Message::OpenConnectionReply1),
+        OpenConnectionRequest2::ID => read(buf, Message::OpenConnectionRequest2),
+        OpenConnectionReply2::ID => read(buf, Message::OpenConnectionReply2),
+        AlreadyConnected::ID => read(buf, Message::AlreadyConnected),
+        IncompatibleProtocolVersion::ID => read(buf, Message::IncompatibleProtocolVersion),
+        NatPunch::ID => read(buf, Message::NatPunch),
+        id => Message::Unknown {
+            id,
+            length: buf.len(),
+        },
+    }
+}
+
+fn connected(buf: &[u8]) -> Message {
+    let id = match buf.first() {
+        Some(id) => *id,
+        None => return Message::User { id: 0, length: 0 },
+    };
+    match id {
+        ConnectedPing::ID => read(buf, Message::ConnectedPing),
+        ConnectedPong::ID => read(buf, Message::ConnectedPong),
+        ConnectionRequest::ID => read(buf, Message::ConnectionRequest),
+        ConnectionRequestAccepted::ID => read(buf, Message::ConnectionRequestAccepted),
+        NewIncomingConnection::ID => read(buf, Message::NewIncomingConnection),
+        Disconnected::ID => read(buf, Message::Disconnected),
+        NatPunchthroughRequest::ID => read(buf, Message::NatPunchthroughRequest),
+        NatConnectAt::ID => read(buf, Message::NatConnectAt),
+        NatPunchthroughFailed::ID => read(buf, Message::NatPunchthroughFailed),
+        RelayRequest::ID => read(buf, Message::RelayRequest),
+        RelayReady::ID => read(buf, Message::RelayReady),
+        RelayData::ID => read(buf, Message::RelayData),
+        RelayClosed::ID => read(buf, Message::RelayClosed),
+        id => Message::User {
+            id,
+            length: buf.len(),
+        },
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FrameSet {
+                header,
+                sequence_number,
+                frames,
+            } => {
+                write!(
+                    f,
+                    "FrameSet header={:#04x} sequence_number={} frames={}",
+                    header,
+                    sequence_number,
+                    frames.len()
+                )?;
+                for (i, x) in frames.iter().enumerate() {
+                    write!(f, "\n  [{}] {}\n      {}", i, x.frame, x.message)?;
+                }
+                Ok(())
+            }
+            Self::Ack(ranges) => write_ranges(f, "ACK", ranges),
+            Self::Nack(ranges) => write_ranges(f, "NACK", ranges),
+            Self::Offline(message) => write!(f, "{}", message),
+            Self::Malformed(error) => write!(f, "malformed datagram: {}", error),
+        }
+    }
+}
+
+fn write_ranges(f: &mut fmt::Formatter<'_>, name: &str, ranges: &[(u32, u32)]) -> fmt::Result {
+    write!(f, "{}", name)?;
+    for (i, (min, max)) in ranges.iter().enumerate() {
+        let sep = if i == 0 { " " } else { ", " };
+        if min == max {
+            write!(f, "{}#{}", sep, min)?;
+        } else {
+            write!(f, "{}#{}..=#{}", sep, min, max)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnconnectedPing(x) => x.fmt(f),
+            Self::UnconnectedPong(x) => x.fmt(f),
+            Self::OpenConnectionRequest1(x) => x.fmt(f),
+            Self::OpenConnectionReply1(x) => x.fmt(f),
+            Self::OpenConnectionRequest2(x) => x.fmt(f),
+            Self::OpenConnectionReply2(x) => x.fmt(f),
+            Self::AlreadyConnected(x) => x.fmt(f),
+            Self::IncompatibleProtocolVersion(x) => x.fmt(f),
+            Self::ConnectedPing(x) => x.fmt(f),
+            Self::ConnectedPong(x) => x.fmt(f),
+            Self::ConnectionRequest(x) => x.fmt(f),
+            Self::ConnectionRequestAccepted(x) => x.fmt(f),
+            Self::NewIncomingConnection(x) => x.fmt(f),
+            Self::Disconnected(x) => x.fmt(f),
+            Self::NatPunchthroughRequest(x) => x.fmt(f),
+            Self::NatConnectAt(x) => x.fmt(f),
+            Self::NatPunchthroughFailed(x) => x.fmt(f),
+            Self::NatPunch(x) => x.fmt(f),
+            Self::RelayRequest(x) => x.fmt(f),
+            Self::RelayReady(x) => x.fmt(f),
+            Self::RelayData(x) => x.fmt(f),
+            Self::RelayClosed(x) => x.fmt(f),
+            Self::Fragment { length } => write!(f, "fragment length={}", length),
+            Self::User { id, length } => write!(f, "user packet id={:#04x} length={}", id, length),
+            Self::Unknown { id, length } => {
+                write!(f, "unknown packet id={:#04x} length={}", id, length)
+            }
+            Self::Malformed { id, error } => {
+                write!(f, "malformed packet id={:#04x}: {}", id, error)
+            }
+        }
+    }
+}
@@ -1,13 +1,27 @@
+#[cfg(all(feature = "batch-io", target_os = "linux"))]
+pub mod batch;
+pub mod capture;
 pub mod client;
+pub mod endpoint;
+pub mod inspect;
+pub mod listener;
 pub(crate) mod macros;
+pub mod metrics;
+pub(crate) mod pacing;
 pub(crate) mod packet;
-pub(crate) mod packetqueue;
+#[doc(hidden)]
+pub mod packetqueue;
 pub mod packets;
+pub mod peer;
 pub mod ping;
+pub mod punch;
 pub(crate) mod reader;
 pub(crate) mod receivedqueue;
+pub mod relay;
+pub mod send;
 pub mod server;
 pub(crate) mod session;
+pub mod transport;
 pub(crate) mod udp;
 pub(crate) mod writer;
 pub const RAKNET_PROTOCOL_VERSION: u8 = 0xA;
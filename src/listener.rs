@@ -0,0 +1,73 @@
+use std::{
+    io::Result,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+pub struct Listener {
+    sockets: Vec<UdpSocket>,
+}
+
+impl Listener {
+    pub fn bind(address: SocketAddr, shards: usize) -> Result<Self> {
+        assert!(shards > 0, "listener needs at least one shard");
+        let mut sockets = vec![bind_socket(address, shards > 1)?];
+        let address = sockets[0].local_addr()?;
+        for _ in 1..shards {
+            sockets.push(bind_socket(address, true)?);
+        }
+        Ok(Self { sockets })
+    }
+
+    pub fn dual_stack(port: u16, shards: usize) -> Result<Self> {
+        let mut v4 = Self::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)), shards)?;
+        let port = v4.sockets[0].local_addr()?.port();
+        let v6 = Self::bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)), shards)?;
+        v4.sockets.extend(v6.sockets);
+        Ok(v4)
+    }
+
+    pub fn from_sockets(sockets: Vec<UdpSocket>) -> Self {
+        assert!(!sockets.is_empty(), "listener needs at least one socket");
+        Self { sockets }
+    }
+
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.sockets
+            .iter()
+            .filter_map(|x| x.local_addr().ok())
+            .collect()
+    }
+
+    pub fn shards(&self) -> usize {
+        self.sockets.len()
+    }
+
+    pub(crate) fn into_sockets(self) -> Vec<UdpSocket> {
+        self.sockets
+    }
+}
+
+fn bind_socket(address: SocketAddr, reuse_port: bool) -> Result<UdpSocket> {
+    let domain = match address {
+        SocketAddr::V4(_) => Domain::ipv4(),
+        SocketAddr::V6(_) => Domain::ipv6(),
+    };
+    let socket = Socket::new(domain, Type::dgram(), Some(Protocol::udp()))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    if reuse_port {
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "SO_REUSEPORT is not supported on this platform",
+        ));
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    Ok(socket.into_udp_socket())
+}
@@ -2,11 +2,72 @@ macro_rules! unwrap_or_return {
     ($res:expr) => {
         match $res {
             Ok(val) => val,
-            Err(_) => {
+            Err(_e) => {
+                crate::macros::trace!(error = %_e, at = stringify!($res), "giving up");
                 return;
             }
         }
     };
 }
 
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)+);
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)+);
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)+);
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)+);
+    };
+}
+
+macro_rules! span {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        let span = crate::macros::Span;
+        span
+    }};
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) type Span = tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub fn entered(self) -> Entered {
+        Entered
+    }
+}
+
+pub(crate) use debug;
+pub(crate) use info;
+pub(crate) use span;
+pub(crate) use trace;
 pub(crate) use unwrap_or_return;
+pub(crate) use warning;
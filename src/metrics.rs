@@ -0,0 +1,293 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeFailure {
+    IncompatibleProtocol,
+    AlreadyConnected,
+    Timeout,
+}
+
+impl HandshakeFailure {
+    pub const ALL: [HandshakeFailure; 3] = [
+        HandshakeFailure::IncompatibleProtocol,
+        HandshakeFailure::AlreadyConnected,
+        HandshakeFailure::Timeout,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            HandshakeFailure::IncompatibleProtocol => 0,
+            HandshakeFailure::AlreadyConnected => 1,
+            HandshakeFailure::Timeout => 2,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HandshakeFailure::IncompatibleProtocol => "incompatible_protocol",
+            HandshakeFailure::AlreadyConnected => "already_connected",
+            HandshakeFailure::Timeout => "timeout",
+        }
+    }
+}
+
+pub struct ServerMetrics {
+    connected_sessions: AtomicU64,
+    handshakes_started: AtomicU64,
+    handshakes_failed: [AtomicU64; 3],
+    datagrams_received: AtomicU64,
+    datagrams_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    socket_errors: AtomicU64,
+    datagrams_dropped: AtomicU64,
+    resends: AtomicU64,
+    worker_sessions: Vec<AtomicU64>,
+    worker_packets: Vec<AtomicU64>,
+}
+
+impl ServerMetrics {
+    pub(crate) fn new(workers: u32) -> Self {
+        Self {
+            connected_sessions: AtomicU64::new(0),
+            handshakes_started: AtomicU64::new(0),
+            handshakes_failed: Default::default(),
+            datagrams_received: AtomicU64::new(0),
+            datagrams_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            socket_errors: AtomicU64::new(0),
+            datagrams_dropped: AtomicU64::new(0),
+            resends: AtomicU64::new(0),
+            worker_sessions: (0..workers).map(|_| AtomicU64::new(0)).collect(),
+            worker_packets: (0..workers).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub fn connected_sessions(&self) -> u64 {
+        self.connected_sessions.load(Ordering::Relaxed)
+    }
+    pub fn handshakes_started(&self) -> u64 {
+        self.handshakes_started.load(Ordering::Relaxed)
+    }
+    pub fn handshakes_failed(&self, reason: HandshakeFailure) -> u64 {
+        self.handshakes_failed[reason.index()].load(Ordering::Relaxed)
+    }
+    pub fn datagrams_received(&self) -> u64 {
+        self.datagrams_received.load(Ordering::Relaxed)
+    }
+    pub fn datagrams_sent(&self) -> u64 {
+        self.datagrams_sent.load(Ordering::Relaxed)
+    }
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+    pub fn socket_errors(&self) -> u64 {
+        self.socket_errors.load(Ordering::Relaxed)
+    }
+    pub fn datagrams_dropped(&self) -> u64 {
+        self.datagrams_dropped.load(Ordering::Relaxed)
+    }
+    pub fn resends(&self) -> u64 {
+        self.resends.load(Ordering::Relaxed)
+    }
+    pub fn worker_sessions(&self) -> Vec<u64> {
+        self.worker_sessions
+            .iter()
+            .map(|x| x.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub fn worker_packets(&self) -> Vec<u64> {
+        self.worker_packets
+            .iter()
+            .map(|x| x.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub(crate) fn session_connected(&self) {
+        self.connected_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn session_disconnected(&self) {
+        self.connected_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+    pub(crate) fn handshake_started(&self) {
+        self.handshakes_started.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn handshake_failed(&self, reason: HandshakeFailure) {
+        self.handshakes_failed[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn received(&self, len: usize) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn sent(&self, len: usize) {
+        self.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn socket_error(&self) {
+        self.socket_errors.fetch_add(1, Ordering::Relaxed);
+    }
+    #[cfg(all(feature = "batch-io", target_os = "linux"))]
+    pub(crate) fn dropped(&self, count: u64) {
+        self.datagrams_dropped.fetch_add(count, Ordering::Relaxed);
+    }
+    pub(crate) fn resent(&self, count: u64) {
+        self.resends.fetch_add(count, Ordering::Relaxed);
+    }
+    pub(crate) fn worker_added(&self, worker: u32) {
+        self.worker_sessions[worker as usize].fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn worker_removed(&self, worker: u32) {
+        self.worker_sessions[worker as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+    pub(crate) fn worker_packet(&self, worker: u32) {
+        self.worker_packets[worker as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ServerMetrics {
+    pub fn openmetrics(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let mut counter = |name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# TYPE raknet_{} counter", name);
+            let _ = writeln!(out, "# HELP raknet_{} {}", name, help);
+            let _ = writeln!(out, "raknet_{}_total {}", name, value);
+        };
+        counter(
+            "handshakes_started",
+            "Handshakes that reached OpenConnectionRequest2.",
+            self.handshakes_started(),
+        );
+        counter(
+            "datagrams_received",
+            "Datagrams received on the server socket.",
+            self.datagrams_received(),
+        );
+        counter(
+            "datagrams_sent",
+            "Datagrams sent from the server socket.",
+            self.datagrams_sent(),
+        );
+        counter(
+            "received_bytes",
+            "Bytes received on the server socket.",
+            self.bytes_received(),
+        );
+        counter(
+            "sent_bytes",
+            "Bytes sent from the server socket.",
+            self.bytes_sent(),
+        );
+        counter(
+            "socket_errors",
+            "Errors returned by the server socket.",
+            self.socket_errors(),
+        );
+        counter(
+            "datagrams_dropped",
+            "Datagrams discarded after the socket refused to send them.",
+            self.datagrams_dropped(),
+        );
+        counter(
+            "resends",
+            "Frame sets sent again after a NACK or a missing ACK.",
+            self.resends(),
+        );
+
+        let _ = writeln!(out, "# TYPE raknet_handshakes_failed counter");
+        let _ = writeln!(
+            out,
+            "# HELP raknet_handshakes_failed Handshakes that were refused or timed out."
+        );
+        for reason in HandshakeFailure::ALL.iter() {
+            let _ = writeln!(
+                out,
+                "raknet_handshakes_failed_total{{reason=\"{}\"}} {}",
+                reason.as_str(),
+                self.handshakes_failed(*reason)
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE raknet_connected_sessions gauge");
+        let _ = writeln!(
+            out,
+            "# HELP raknet_connected_sessions Sessions that completed the handshake."
+        );
+        let _ = writeln!(
+            out,
+            "raknet_connected_sessions {}",
+            self.connected_sessions()
+        );
+
+        let _ = writeln!(out, "# TYPE raknet_worker_sessions gauge");
+        let _ = writeln!(
+            out,
+            "# HELP raknet_worker_sessions Sessions placed on each session worker."
+        );
+        for (worker, sessions) in self.worker_sessions().iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "raknet_worker_sessions{{worker=\"{}\"}} {}",
+                worker, sessions
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE raknet_worker_datagrams counter");
+        let _ = writeln!(
+            out,
+            "# HELP raknet_worker_datagrams Datagrams handled by sessions on each session worker."
+        );
+        for (worker, packets) in self.worker_packets().iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "raknet_worker_datagrams_total{{worker=\"{}\"}} {}",
+                worker, packets
+            );
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub async fn serve(
+    metrics: std::sync::Arc<ServerMetrics>,
+    addr: std::net::SocketAddr,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut listener = tokio::net::TcpListener::bind(addr).await?;
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buff = [0u8; 1024];
+            let length = match stream.read(&mut buff).await {
+                Ok(length) => length,
+                Err(_) => return,
+            };
+            let request = String::from_utf8_lossy(&buff[..length]);
+            let mut line = request.lines().next().unwrap_or_default().split(' ');
+            let response = match (line.next(), line.next()) {
+                (Some("GET"), Some("/metrics")) => {
+                    let body = metrics.openmetrics();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_owned(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
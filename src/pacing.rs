@@ -0,0 +1,67 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::send::RateLimit;
+
+pub(crate) type SharedBucket = Arc<Mutex<TokenBucket>>;
+
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last: Instant::now(),
+        }
+    }
+    pub fn shared(limit: RateLimit) -> SharedBucket {
+        Arc::new(Mutex::new(Self::new(limit)))
+    }
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.bytes_per_sec as f64).min(self.limit.burst as f64);
+        self.last = now;
+    }
+    fn ready(&mut self) -> bool {
+        self.refill();
+        self.tokens > 0.0
+    }
+    fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+pub(crate) fn admit(
+    local: &mut Option<TokenBucket>,
+    global: Option<&SharedBucket>,
+    bytes: usize,
+) -> bool {
+    let mut global = global.map(|x| x.lock().unwrap());
+    if !local.as_mut().is_none_or(|x| x.ready()) || !global.as_mut().is_none_or(|x| x.ready()) {
+        return false;
+    }
+    charge(local, global.as_deref_mut(), bytes);
+    true
+}
+
+pub(crate) fn charge(
+    local: &mut Option<TokenBucket>,
+    global: Option<&mut TokenBucket>,
+    bytes: usize,
+) {
+    if let Some(local) = local {
+        local.consume(bytes);
+    }
+    if let Some(global) = global {
+        global.consume(bytes);
+    }
+}
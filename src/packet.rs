@@ -3,9 +3,12 @@ use std::{
     io::Result,
 };
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
-use crate::packets::{frame::Frame, Reliability};
+use crate::{
+    macros::warning,
+    packets::{frame::Frame, Reliability},
+};
 
 pub(crate) struct ACKQueue {
     lowest: u32,
@@ -52,12 +55,13 @@ impl ACKQueue {
     }
 }
 
-#[derive(Clone)]
 pub(crate) struct SplitPacket {
     pub split_size: u32,
     pub data: HashMap<u32, Frame>,
     pub reliability: Reliability,
     pub order_index: u32,
+    pub sequence_index: u32,
+    pub order_channel: u8,
     full: bool,
 }
 impl SplitPacket {
@@ -67,6 +71,8 @@ impl SplitPacket {
             data: HashMap::new(),
             reliability,
             order_index,
+            sequence_index: 0,
+            order_channel: 0,
             full: false,
         }
     }
@@ -76,21 +82,30 @@ impl SplitPacket {
             if self.data.len() as u32 == self.split_size {
                 self.full = true;
             }
+        } else {
+            warning!(
+                index,
+                split_size = self.split_size,
+                "split index out of range"
+            );
         }
     }
     pub fn is_full(&self) -> bool {
         self.full
     }
-    pub fn get_all(&mut self) -> BytesMut {
-        let mut ret = BytesMut::new();
+    pub fn get_all(&mut self) -> Bytes {
+        let len = self.data.values().map(|x| x.data.len()).sum();
+        let mut ret = BytesMut::with_capacity(len);
         for index in 0..self.split_size {
             ret.extend_from_slice(&self.data.get(&index).unwrap().data);
         }
-        ret
+        ret.freeze()
     }
     pub fn get_frame(&mut self) -> Result<Frame> {
         let mut frame = Frame::new(self.reliability.clone(), self.get_all());
         frame.order_index = self.order_index;
+        frame.sequence_index = self.sequence_index;
+        frame.order_channel = self.order_channel;
         Ok(frame)
     }
 }
@@ -115,10 +130,11 @@ impl SplitPacketQueue {
         if let std::collections::hash_map::Entry::Vacant(e) = self.pool.entry(frame.split_id) {
             let mut new_split = SplitPacket::new(
                 frame.split_count,
-                frame.sequence_index,
+                frame.order_index,
                 frame.reliability.clone(),
             );
-            new_split.order_index = frame.order_index;
+            new_split.sequence_index = frame.sequence_index;
+            new_split.order_channel = frame.order_channel;
             e.insert(new_split);
         }
         self.pool
@@ -127,15 +143,14 @@ impl SplitPacketQueue {
             .add(frame.split_index, frame);
     }
     pub fn get_and_clear(&mut self) -> Vec<SplitPacket> {
-        let mut ret = vec![];
         for water in self.pool.iter() {
             if water.1.is_full() {
                 self.delete.push(*water.0);
-                ret.push(water.1.clone());
             }
         }
-        for delete in self.delete.iter() {
-            self.pool.remove(delete);
+        let mut ret = vec![];
+        for delete in self.delete.drain(..) {
+            ret.extend(self.pool.remove(&delete));
         }
         ret
     }
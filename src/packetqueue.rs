@@ -1,54 +1,171 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+use bytes::{Bytes, BytesMut};
+use futures::channel::oneshot;
 
 use actix::clock::Instant;
 
-use crate::packets::{frame::Frame, frame_set::FrameSet};
+use crate::{
+    packets::{frame::Frame, frame_set::FrameSet},
+    send::{Coalescing, Delivery, Priority, QueueDepth, ReceiptSender},
+};
 
 const NEEDS_B_AND_AS_FLAG: u8 = 0x4;
 
 const CONTINUOUS_SEND_FLAG: u8 = 0x8;
 
+const PRIORITY_WEIGHTS: [usize; 3] = [4, 2, 1];
+
+type Queued = (Frame, Option<(u32, bool)>);
+
 pub struct PacketQueue {
     pub queue: HashMap<u32, FrameSet>,
+    encoded: HashMap<u32, Bytes>,
     pub time_passed: HashMap<u32, (Instant, bool)>,
     pub max: u32,
     send_min: u32,
     resend: Vec<u32>,
     set_size: usize,
     set_queue: Vec<Frame>,
+    queued: [VecDeque<Queued>; 3],
+    queued_bytes: usize,
+    queued_since: Option<Instant>,
+    released: bool,
+    cursor: (usize, usize),
+    coalescing: Coalescing,
     mtu: u16,
+    resent: u64,
+    depth: QueueDepth,
+    set_receipts: Vec<(u32, bool)>,
+    receipts: HashMap<u32, Vec<(u32, bool)>>,
+    pending: HashMap<u32, (usize, oneshot::Sender<Delivery>)>,
 }
 
 impl PacketQueue {
     pub fn new(mtu: u16) -> Self {
         Self {
             queue: HashMap::new(),
+            encoded: HashMap::new(),
             time_passed: HashMap::new(),
             max: 0,
             send_min: 0,
             resend: vec![],
             set_size: 0,
             set_queue: vec![],
+            queued: Default::default(),
+            queued_bytes: 0,
+            queued_since: None,
+            released: false,
+            cursor: (0, 0),
+            coalescing: Coalescing::default(),
             mtu,
+            resent: 0,
+            depth: QueueDepth::default(),
+            set_receipts: vec![],
+            receipts: HashMap::new(),
+            pending: HashMap::new(),
         }
     }
-    pub fn add_frame(&mut self, frame: Frame) {
-        if self.set_size + frame.length() < (self.mtu - 42) as usize && !frame.split {
-            self.set_size += frame.length();
-            self.set_queue.push(frame);
-        } else {
+    pub(crate) fn track(&mut self, receipt: ReceiptSender) {
+        self.pending.insert(receipt.id, (0, receipt.tx));
+    }
+    pub fn set_coalescing(&mut self, coalescing: Coalescing) {
+        self.coalescing = coalescing;
+    }
+    pub fn add_frame(&mut self, frame: Frame, receipt: Option<u32>, priority: Priority) {
+        self.depth.frames += 1;
+        self.depth.bytes += frame.length();
+        let receipt = receipt.map(|x| (x, frame.reliability.reliable()));
+        // count every frame up front so that a partly sent message cannot
+        // resolve before its remaining frames are sealed
+        if let Some(pending) = receipt.and_then(|x| self.pending.get_mut(&x.0)) {
+            pending.0 += 1;
+        }
+        match priority {
+            Priority::Immediate => {
+                self.pack(frame, receipt);
+                self.seal();
+            }
+            priority => {
+                self.queued_bytes += frame.length();
+                self.queued_since.get_or_insert_with(Instant::now);
+                let index = match priority {
+                    Priority::High => 0,
+                    Priority::Medium => 1,
+                    _ => 2,
+                };
+                self.queued[index].push_back((frame, receipt));
+            }
+        }
+    }
+    pub fn should_flush(&self) -> bool {
+        self.queued_since.is_some()
+            && (!self.coalescing.enabled || self.queued_bytes >= self.coalescing.max_bytes)
+    }
+    fn due(&self) -> bool {
+        match self.queued_since {
+            Some(since) => self.should_flush() || since.elapsed() >= self.coalescing.max_delay,
+            None => false,
+        }
+    }
+    pub fn flush(&mut self) {
+        self.released = self.queued.iter().any(|x| !x.is_empty());
+        self.queued_bytes = 0;
+        self.queued_since = None;
+    }
+    fn pick(&mut self) -> Option<usize> {
+        if self.queued.iter().all(|x| x.is_empty()) {
+            return None;
+        }
+        loop {
+            let (index, taken) = self.cursor;
+            if taken < PRIORITY_WEIGHTS[index] && !self.queued[index].is_empty() {
+                return Some(index);
+            }
+            self.cursor = ((index + 1) % PRIORITY_WEIGHTS.len(), 0);
+        }
+    }
+    fn assemble(&mut self) -> Vec<(usize, Queued)> {
+        let mut set = vec![];
+        let mut size = 0;
+        let mut cursor = self.cursor;
+        while let Some(index) = self.pick() {
+            let length = self.queued[index][0].0.length();
+            if !set.is_empty() && size + length >= (self.mtu - 42) as usize {
+                self.cursor = cursor;
+                break;
+            }
+            size += length;
+            self.cursor.1 += 1;
+            set.push((index, self.queued[index].pop_front().unwrap()));
+            cursor = self.cursor;
+        }
+        set
+    }
+    fn pack(&mut self, frame: Frame, receipt: Option<(u32, bool)>) {
+        if self.set_size + frame.length() >= (self.mtu - 42) as usize {
+            self.seal();
+        }
+        self.set_size += frame.length();
+        self.set_queue.push(frame);
+        self.set_receipts.extend(receipt);
+    }
+    fn seal(&mut self) {
+        if !self.set_queue.is_empty() {
+            let header = if self.set_queue.iter().any(|x| x.split) {
+                0x80 | NEEDS_B_AND_AS_FLAG | CONTINUOUS_SEND_FLAG
+            } else {
+                0x80 | NEEDS_B_AND_AS_FLAG
+            };
             let set = FrameSet {
-                header: {
-                    if frame.split {
-                        0x80 | NEEDS_B_AND_AS_FLAG | CONTINUOUS_SEND_FLAG
-                    } else {
-                        0x80 | NEEDS_B_AND_AS_FLAG
-                    }
-                },
+                header,
                 sequence_number: self.max,
-                datas: vec![frame],
+                datas: std::mem::take(&mut self.set_queue),
             };
+            let receipts = std::mem::take(&mut self.set_receipts);
+            self.attach(self.max, receipts);
             self.add(set);
+            self.set_size = 0;
         }
     }
     pub fn add(&mut self, frame_set: FrameSet) {
@@ -56,25 +173,30 @@ impl PacketQueue {
             self.max += 1;
             self.time_passed
                 .insert(frame_set.sequence_number, (Instant::now(), false));
+            self.encoded
+                .insert(frame_set.sequence_number, frame_set.encode().freeze());
             self.queue.insert(frame_set.sequence_number, frame_set);
         }
     }
     pub fn received(&mut self, sequence: u32) {
-        if self.queue.contains_key(&sequence) {
-            self.queue.remove(&sequence);
+        if sequence >= self.send_min || !self.time_passed.get(&sequence).is_some_and(|x| x.1) {
+            return;
+        }
+        if let Some(frame_set) = self.queue.remove(&sequence) {
             self.time_passed.remove(&sequence);
+            self.encoded.remove(&sequence);
+            for frame in frame_set.datas.iter() {
+                self.depth.frames -= 1;
+                self.depth.bytes -= frame.length();
+            }
+            for (receipt, _) in self.receipts.remove(&sequence).unwrap_or_default() {
+                self.acked(receipt);
+            }
         }
     }
     pub fn tick(&mut self) {
-        if !self.set_queue.is_empty() {
-            let set = FrameSet {
-                header: 0x80 | NEEDS_B_AND_AS_FLAG,
-                sequence_number: self.max,
-                datas: self.set_queue.clone(),
-            };
-            self.add(set);
-            self.set_queue.clear();
-            self.set_size = 0;
+        if self.due() {
+            self.flush();
         }
         let time_passed = Instant::now();
         for elem in self.time_passed.iter_mut() {
@@ -84,40 +206,140 @@ impl PacketQueue {
         }
     }
     pub fn readd(&mut self) {
-        for resend in self.resend.iter() {
-            if self.queue.contains_key(resend) {
-                let mut added = self.queue.get_mut(resend).unwrap().clone();
-                added.sequence_number = self.max;
-                self.queue.insert(self.max, added);
-                self.queue.remove(resend);
-                self.time_passed.remove(resend);
-                self.time_passed.insert(self.max, (Instant::now(), false));
-                self.max += 1;
-            }
+        for resend in std::mem::take(&mut self.resend) {
+            self.requeue(resend);
         }
-        self.resend.clear();
     }
     pub fn resend(&mut self, index: u32) {
-        if self.time_passed.contains_key(&index) {
-            let mut added = self.queue.get_mut(&index).unwrap().clone();
-            added.sequence_number = self.max;
-            self.queue.insert(self.max, added);
-            self.queue.remove(&index);
-            self.time_passed.remove(&index);
-            self.time_passed.insert(self.max, (Instant::now(), false));
-            self.max += 1;
+        if self.time_passed.get(&index).is_some_and(|x| x.1) {
+            self.requeue(index);
+        }
+    }
+    fn requeue(&mut self, index: u32) {
+        let mut added = match self.queue.remove(&index) {
+            Some(frame_set) => frame_set,
+            None => return,
+        };
+        self.time_passed.remove(&index);
+        let encoded = self.encoded.remove(&index).unwrap();
+        let stripped = added.datas.iter().any(|x| !x.reliability.reliable());
+        for frame in added.datas.iter().filter(|x| !x.reliability.reliable()) {
+            self.depth.frames -= 1;
+            self.depth.bytes -= frame.length();
+        }
+        added.datas.retain(|x| x.reliability.reliable());
+        let mut receipts = self.receipts.remove(&index).unwrap_or_default();
+        for (receipt, _) in receipts.iter().filter(|x| !x.1) {
+            self.lost(*receipt);
+        }
+        receipts.retain(|x| x.1);
+        if added.datas.is_empty() {
+            return;
+        }
+        added.sequence_number = self.max;
+        let encoded = if stripped {
+            added.encode()
+        } else {
+            let mut encoded = BytesMut::from(&encoded[..]);
+            encoded[1..4].copy_from_slice(&self.max.to_le_bytes()[..3]);
+            encoded
+        };
+        self.encoded.insert(self.max, encoded.freeze());
+        self.queue.insert(self.max, added);
+        self.time_passed.insert(self.max, (Instant::now(), false));
+        if !receipts.is_empty() {
+            self.receipts.insert(self.max, receipts);
+        }
+        self.max += 1;
+        self.resent += 1;
+    }
+    fn attach(&mut self, sequence: u32, receipts: Vec<(u32, bool)>) {
+        if !receipts.is_empty() {
+            self.receipts.insert(sequence, receipts);
+        }
+    }
+    fn acked(&mut self, receipt: u32) {
+        if let Some(pending) = self.pending.get_mut(&receipt) {
+            pending.0 -= 1;
+            if pending.0 == 0 {
+                let (_, tx) = self.pending.remove(&receipt).unwrap();
+                let _ = tx.send(Delivery::Acked);
+            }
+        }
+    }
+    pub fn abandon(&mut self) {
+        for (_, (_, tx)) in self.pending.drain() {
+            let _ = tx.send(Delivery::Lost);
+        }
+    }
+    fn lost(&mut self, receipt: u32) {
+        if let Some((_, tx)) = self.pending.remove(&receipt) {
+            let _ = tx.send(Delivery::Lost);
         }
     }
-    pub fn get_packet(&mut self) -> Vec<FrameSet> {
+    pub fn depth(&self) -> QueueDepth {
+        self.depth
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+            && self.set_queue.is_empty()
+            && self.queued.iter().all(|x| x.is_empty())
+    }
+    pub fn take_resent(&mut self) -> u64 {
+        std::mem::take(&mut self.resent)
+    }
+    pub fn get_packet<F: FnMut(usize) -> bool>(&mut self, allow: F) -> Vec<Bytes> {
         //get send able packets and start timer
         self.tick();
         self.readd();
+        self.take_sendable(allow)
+    }
+    pub fn take_sendable<F: FnMut(usize) -> bool>(&mut self, mut allow: F) -> Vec<Bytes> {
         let mut ret = vec![];
-        for i in self.send_min..self.max {
-            ret.push((*self.queue.get(&i).unwrap()).clone());
-            self.time_passed.get_mut(&i).unwrap().1 = true;
+        while self.send_min < self.max {
+            let encoded = match self.encoded.get(&self.send_min) {
+                Some(encoded) => encoded,
+                None => {
+                    self.send_min += 1;
+                    continue;
+                }
+            };
+            if !allow(encoded.len()) {
+                return ret;
+            }
+            ret.push(self.emit());
+        }
+        // frames stay in their priority queues until the pacer admits them so
+        // that a later high priority frame can overtake a paced backlog
+        while self.released {
+            let cursor = self.cursor;
+            let set = self.assemble();
+            if set.is_empty() {
+                self.released = false;
+                self.cursor = (0, 0);
+                break;
+            }
+            let size = 4 + set.iter().map(|x| x.1 .0.length()).sum::<usize>();
+            if !allow(size) {
+                self.cursor = cursor;
+                for (index, queued) in set.into_iter().rev() {
+                    self.queued[index].push_front(queued);
+                }
+                break;
+            }
+            for (_, (frame, receipt)) in set {
+                self.pack(frame, receipt);
+            }
+            self.seal();
+            ret.push(self.emit());
         }
-        self.send_min = self.max;
         ret
     }
+    fn emit(&mut self) -> Bytes {
+        let encoded = self.encoded[&self.send_min].clone();
+        self.time_passed
+            .insert(self.send_min, (Instant::now(), true));
+        self.send_min += 1;
+        encoded
+    }
 }
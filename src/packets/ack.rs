@@ -5,9 +5,9 @@ use crate::{
 };
 use actix::prelude::*;
 use bytes::BytesMut;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct Ack {
     pub record_count: u16,
@@ -50,14 +50,13 @@ impl Packet for Ack {
         let mut cursor = Reader::new(payload);
         let record_count = cursor.read_u16(Endian::Big)?;
         let max_equals_min = cursor.read_u8()? != 0;
-        let sequences;
         let sequence = cursor.read_u24(Endian::Little)?;
-        if max_equals_min {
-            sequences = (sequence, sequence)
+        let sequences = if max_equals_min {
+            (sequence, sequence)
         } else {
             let sequence_max = cursor.read_u24(Endian::Little)?;
-            sequences = (sequence, sequence_max)
-        }
+            (sequence, sequence_max)
+        };
         Ok(Self {
             record_count,
             max_equals_min,
@@ -65,3 +64,13 @@ impl Packet for Ack {
         })
     }
 }
+
+impl fmt::Display for Ack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.max_equals_min {
+            write!(f, "ACK #{}", self.sequences.0)
+        } else {
+            write!(f, "ACK #{}..=#{}", self.sequences.0, self.sequences.1)
+        }
+    }
+}
@@ -4,9 +4,9 @@ use crate::{
     writer::Writer,
 };
 use bytes::BytesMut;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct AlreadyConnected {
     _magic: bool,
     pub guid: u64,
@@ -33,3 +33,9 @@ impl Packet for AlreadyConnected {
         cursor.write_u64(self.guid, Endian::Big);
     }
 }
+
+impl fmt::Display for AlreadyConnected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AlreadyConnected guid={:#x}", self.guid)
+    }
+}
@@ -4,9 +4,9 @@ use crate::{
     writer::Writer,
 };
 use bytes::BytesMut;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ConnectedPing {
     pub client_timestamp: i64,
 }
@@ -32,3 +32,13 @@ impl Packet for ConnectedPing {
         cursor.write_i64(self.client_timestamp, Endian::Big);
     }
 }
+
+impl fmt::Display for ConnectedPing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ConnectedPing client_timestamp={}",
+            self.client_timestamp
+        )
+    }
+}
@@ -3,9 +3,9 @@ use bytes::BytesMut;
 use crate::packets::Packet;
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ConnectedPong {
     pub client_timestamp: i64,
     pub server_timestamp: i64,
@@ -35,3 +35,13 @@ impl Packet for ConnectedPong {
         cursor.write_i64(self.server_timestamp, Endian::Big);
     }
 }
+
+impl fmt::Display for ConnectedPong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ConnectedPong client_timestamp={} server_timestamp={}",
+            self.client_timestamp, self.server_timestamp
+        )
+    }
+}
@@ -1,15 +1,16 @@
 use bytes::BytesMut;
 
-use crate::packets::Packet;
+use crate::packets::{Packet, COOKIE_EXTENSION};
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ConnectionRequest {
     pub guid: u64,
     pub time: i64,
     pub use_encryption: u8,
+    pub cookie: Option<u64>,
 }
 
 impl ConnectionRequest {
@@ -18,8 +19,13 @@ impl ConnectionRequest {
             guid,
             time,
             use_encryption: use_encryption as u8,
+            cookie: None,
         }
     }
+    pub fn with_cookie(mut self, cookie: u64) -> Self {
+        self.cookie = Some(cookie);
+        self
+    }
 }
 
 impl Packet for ConnectionRequest {
@@ -30,6 +36,10 @@ impl Packet for ConnectionRequest {
             guid: cursor.read_u64(Endian::Big)?,
             time: cursor.read_i64(Endian::Big)?,
             use_encryption: cursor.read_u8()?,
+            cookie: match (payload.len() as u64 - cursor.pos(), cursor.read_u8()) {
+                (9, Ok(COOKIE_EXTENSION)) => Some(cursor.read_u64(Endian::Big)?),
+                _ => None,
+            },
         })
     }
     fn write(&self, bytes: &mut BytesMut) {
@@ -37,5 +47,23 @@ impl Packet for ConnectionRequest {
         cursor.write_u64(self.guid, Endian::Big);
         cursor.write_i64(self.time, Endian::Big);
         cursor.write_u8(self.use_encryption);
+        if let Some(cookie) = self.cookie {
+            cursor.write_u8(COOKIE_EXTENSION);
+            cursor.write_u64(cookie, Endian::Big);
+        }
+    }
+}
+
+impl fmt::Display for ConnectionRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ConnectionRequest guid={:#x} time={} use_encryption={}",
+            self.guid, self.time, self.use_encryption
+        )?;
+        if let Some(cookie) = self.cookie {
+            write!(f, " cookie={:#x}", cookie)?;
+        }
+        Ok(())
     }
 }
@@ -1,9 +1,9 @@
 use crate::packets::Packet;
 use crate::reader::{Endian::Big, Reader};
 use crate::writer::Writer;
-use std::{io::Result, net::SocketAddr};
+use std::{fmt, io::Result, net::SocketAddr};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ConnectionRequestAccepted {
     pub client_address: SocketAddr,
     pub system_index: u16,
@@ -53,3 +53,13 @@ impl Packet for ConnectionRequestAccepted {
         cursor.write_i64(self.accepted_timestamp, Big);
     }
 }
+
+impl fmt::Display for ConnectionRequestAccepted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ConnectionRequestAccepted client_address={} system_index={} request_timestamp={} accepted_timestamp={}",
+            self.client_address, self.system_index, self.request_timestamp, self.accepted_timestamp
+        )
+    }
+}
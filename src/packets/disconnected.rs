@@ -1,9 +1,9 @@
 use bytes::BytesMut;
 
 use crate::packets::Packet;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Disconnected;
 
 impl Packet for Disconnected {
@@ -13,3 +13,9 @@ impl Packet for Disconnected {
     }
     fn write(&self, _bytes: &mut BytesMut) {}
 }
+
+impl fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Disconnected")
+    }
+}
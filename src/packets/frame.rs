@@ -1,6 +1,6 @@
-use std::io::Result;
+use std::{fmt, io::Result};
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 use super::Reliability;
 use crate::{
@@ -13,33 +13,35 @@ use crate::{
 use std::io::Read;
 const SPLIT_FLAG: u8 = 0x10;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Frame {
     pub reliability: Reliability,
 
     pub message_index: u32,
     pub sequence_index: u32,
     pub order_index: u32,
+    pub order_channel: u8,
 
     pub split: bool,
     pub split_count: u32,
     pub split_index: u32,
     pub split_id: u16,
-    pub data: BytesMut,
+    pub data: Bytes,
 }
 
 impl Frame {
-    pub fn new(reliability: Reliability, data: BytesMut) -> Self {
+    pub fn new<B: Into<Bytes>>(reliability: Reliability, data: B) -> Self {
         Self {
             reliability,
             message_index: 0,
             sequence_index: 0,
             order_index: 0,
+            order_channel: 0,
             split: false,
             split_count: 0,
             split_index: 0,
             split_id: 0,
-            data,
+            data: data.into(),
         }
     }
     pub fn length(&self) -> usize {
@@ -62,18 +64,29 @@ impl Frame {
         ret
     }
     pub fn decode<T: Read + AsRef<[u8]>>(cursor: &mut Reader<T>) -> Result<Self> {
+        let (mut packet, length) = Self::decode_header(cursor)?;
+        let mut data = BytesMut::new();
+        data.resize(length, 0x0);
+        cursor.read(&mut data)?;
+        packet.data = data.freeze();
+        Ok(packet)
+    }
+    pub(crate) fn decode_header<T: Read + AsRef<[u8]>>(
+        cursor: &mut Reader<T>,
+    ) -> Result<(Self, usize)> {
         let mut packet = Self {
             reliability: Reliability::new(0)?,
 
             message_index: 0,
             sequence_index: 0,
             order_index: 0,
+            order_channel: 0,
 
             split: false,
             split_count: 0,
             split_index: 0,
             split_id: 0,
-            data: BytesMut::new(),
+            data: Bytes::new(),
         };
 
         let header = cursor.read_u8()?;
@@ -92,7 +105,7 @@ impl Frame {
 
         if packet.reliability.sequenced_or_ordered() {
             packet.order_index = cursor.read_u24(Endian::Little)?;
-            cursor.next(1);
+            packet.order_channel = cursor.read_u8()?;
         }
 
         if packet.split {
@@ -100,9 +113,7 @@ impl Frame {
             packet.split_id = cursor.read_u16(Endian::Big)?;
             packet.split_index = cursor.read_u32(Endian::Big)?;
         }
-        packet.data.resize(packet_length as usize, 0x0);
-        cursor.read(&mut packet.data)?;
-        Ok(packet)
+        Ok((packet, packet_length as usize))
     }
 
     pub fn encode(&self, bytes: &mut BytesMut) {
@@ -121,7 +132,7 @@ impl Frame {
         }
         if self.reliability.sequenced_or_ordered() {
             cursor.write_u24(self.order_index, Endian::Little);
-            cursor.write_u8(0);
+            cursor.write_u8(self.order_channel);
         }
         if self.split {
             cursor.write_u32(self.split_count, Endian::Big);
@@ -131,3 +142,32 @@ impl Frame {
         cursor.write(&self.data);
     }
 }
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reliability)?;
+        if self.reliability.reliable() {
+            write!(f, " message_index={}", self.message_index)?;
+        }
+        if self.reliability.sequenced() {
+            write!(f, " sequence_index={}", self.sequence_index)?;
+        }
+        if self.reliability.sequenced_or_ordered() {
+            write!(
+                f,
+                " order_index={} order_channel={}",
+                self.order_index, self.order_channel
+            )?;
+        }
+        if self.split {
+            write!(
+                f,
+                " split={}/{} split_id={}",
+                self.split_index + 1,
+                self.split_count,
+                self.split_id
+            )?;
+        }
+        write!(f, " length={}", self.data.len())
+    }
+}
@@ -4,10 +4,13 @@ use crate::{
     writer::Writer,
 };
 use actix::prelude::*;
-use bytes::BytesMut;
-use std::io::Result;
+use bytes::{Bytes, BytesMut};
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+};
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct FrameSet {
     pub header: u8,
@@ -30,6 +33,30 @@ impl FrameSet {
 
         Ok(frame_set)
     }
+    pub fn decode_bytes(payload: &Bytes) -> Result<Self> {
+        let size = payload.len();
+        let mut cursor = Reader::new(&payload[..]);
+        let mut frame_set = Self {
+            header: cursor.read_u8()?,
+            sequence_number: cursor.read_u24(Little)?,
+            datas: vec![],
+        };
+        while cursor.pos() < size as u64 {
+            let (mut frame, length) = Frame::decode_header(&mut cursor)?;
+            let pos = cursor.pos() as usize;
+            if pos + length > size {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            frame.data = payload.slice(pos..pos + length);
+            cursor.next(length as u64);
+            frame_set.datas.push(frame)
+        }
+
+        Ok(frame_set)
+    }
+    pub fn length(&self) -> usize {
+        4 + self.datas.iter().map(|x| x.length()).sum::<usize>()
+    }
     pub fn encode(&self) -> BytesMut {
         let mut bytes = BytesMut::new();
         let mut cursor = Writer::new(&mut bytes);
@@ -41,3 +68,15 @@ impl FrameSet {
         bytes
     }
 }
+
+impl fmt::Display for FrameSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FrameSet header={:#04x} sequence_number={} frames={}",
+            self.header,
+            self.sequence_number,
+            self.datas.len()
+        )
+    }
+}
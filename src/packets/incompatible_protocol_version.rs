@@ -3,9 +3,9 @@ use bytes::BytesMut;
 use crate::packets::Packet;
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct IncompatibleProtocolVersion {
     pub server_protocol: u8,
     _magic: bool,
@@ -39,3 +39,13 @@ impl Packet for IncompatibleProtocolVersion {
         cursor.write_u64(self.server_guid, Endian::Big);
     }
 }
+
+impl fmt::Display for IncompatibleProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "IncompatibleProtocolVersion server_protocol={} server_guid={:#x}",
+            self.server_protocol, self.server_guid
+        )
+    }
+}
@@ -9,11 +9,19 @@ pub(crate) mod frame;
 pub(crate) mod frame_set;
 pub(crate) mod incompatible_protocol_version;
 pub(crate) mod nack;
+pub(crate) mod nat_connect_at;
+pub(crate) mod nat_punch;
+pub(crate) mod nat_punchthrough_failed;
+pub(crate) mod nat_punchthrough_request;
 pub(crate) mod new_incoming_connection;
 pub(crate) mod open_connection_reply1;
 pub(crate) mod open_connection_reply2;
 pub(crate) mod open_connection_request1;
 pub(crate) mod open_connection_request2;
+pub(crate) mod relay_closed;
+pub(crate) mod relay_data;
+pub(crate) mod relay_ready;
+pub(crate) mod relay_request;
 pub(crate) mod unconnected_ping;
 pub(crate) mod unconnected_pong;
 
@@ -29,23 +37,34 @@ pub use frame::*;
 pub use frame_set::*;
 pub use incompatible_protocol_version::*;
 pub use nack::*;
+pub use nat_connect_at::*;
+pub use nat_punch::*;
+pub use nat_punchthrough_failed::*;
+pub use nat_punchthrough_request::*;
 pub use new_incoming_connection::*;
 pub use open_connection_reply1::*;
 pub use open_connection_reply2::*;
 pub use open_connection_request1::*;
 pub use open_connection_request2::*;
+pub use relay_closed::*;
+pub use relay_data::*;
+pub use relay_ready::*;
+pub use relay_request::*;
 pub use unconnected_ping::*;
 pub use unconnected_pong::*;
 
-use std::io::{Error, ErrorKind};
+use std::{fmt, io::Error};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Reliability {
     Unreliable,
     UnreliableSequenced,
     Reliable,
     ReliableOrdered,
     ReliableSequenced,
+    UnreliableWithAckReceipt,
+    ReliableWithAckReceipt,
+    ReliableOrderedWithAckReceipt,
 }
 
 impl Reliability {
@@ -56,10 +75,10 @@ impl Reliability {
             0x2 => Ok(Self::Reliable),
             0x3 => Ok(Self::ReliableOrdered),
             0x4 => Ok(Self::ReliableSequenced),
-            _ => Err(Error::new(
-                ErrorKind::Other,
-                format!("unknown reliability byte {}", &byte),
-            )),
+            0x5 => Ok(Self::UnreliableWithAckReceipt),
+            0x6 => Ok(Self::ReliableWithAckReceipt),
+            0x7 => Ok(Self::ReliableOrderedWithAckReceipt),
+            _ => Err(Error::other(format!("unknown reliability byte {}", &byte))),
         }
     }
     pub(crate) fn to_byte(&self) -> u8 {
@@ -69,13 +88,20 @@ impl Reliability {
             Self::Reliable => 0x2,
             Self::ReliableOrdered => 0x3,
             Self::ReliableSequenced => 0x4,
+            Self::UnreliableWithAckReceipt => 0x0,
+            Self::ReliableWithAckReceipt => 0x2,
+            Self::ReliableOrderedWithAckReceipt => 0x3,
         }
     }
 
     pub(crate) fn reliable(&self) -> bool {
         matches!(
             self,
-            Reliability::Reliable | Reliability::ReliableOrdered | Reliability::ReliableSequenced
+            Reliability::Reliable
+                | Reliability::ReliableOrdered
+                | Reliability::ReliableSequenced
+                | Reliability::ReliableWithAckReceipt
+                | Reliability::ReliableOrderedWithAckReceipt
         )
     }
 
@@ -85,6 +111,7 @@ impl Reliability {
             Reliability::UnreliableSequenced
                 | Reliability::ReliableOrdered
                 | Reliability::ReliableSequenced
+                | Reliability::ReliableOrderedWithAckReceipt
         )
     }
 
@@ -96,6 +123,26 @@ impl Reliability {
     }
 }
 
+impl fmt::Display for Reliability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Unreliable => "Unreliable",
+            Self::UnreliableSequenced => "UnreliableSequenced",
+            Self::Reliable => "Reliable",
+            Self::ReliableOrdered => "ReliableOrdered",
+            Self::ReliableSequenced => "ReliableSequenced",
+            Self::UnreliableWithAckReceipt => "UnreliableWithAckReceipt",
+            Self::ReliableWithAckReceipt => "ReliableWithAckReceipt",
+            Self::ReliableOrderedWithAckReceipt => "ReliableOrderedWithAckReceipt",
+        };
+        f.write_str(name)
+    }
+}
+
+pub const ORDER_CHANNELS: u8 = 32;
+
+pub const COOKIE_EXTENSION: u8 = 0xc7;
+
 pub const MAGIC: [u8; 16] = [
     0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
 ];
@@ -118,5 +165,9 @@ pub fn encode<T: Packet>(packet: T) -> BytesMut {
 }
 
 pub fn decode<T: Packet>(buf: &[u8]) -> Result<T> {
-    T::read(&buf[1..])
+    let packet = T::read(&buf[1..]);
+    if let Err(_e) = &packet {
+        crate::macros::debug!(packet_id = T::ID, len = buf.len(), error = %_e, "failed to decode packet");
+    }
+    packet
 }
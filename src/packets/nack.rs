@@ -1,4 +1,4 @@
-use std::io::Result;
+use std::{fmt, io::Result};
 
 use actix::prelude::*;
 use bytes::BytesMut;
@@ -9,7 +9,7 @@ use crate::{
     writer::Writer,
 };
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct Nack {
     pub record_count: u16,
@@ -52,14 +52,13 @@ impl Packet for Nack {
         let mut cursor = Reader::new(payload);
         let record_count = cursor.read_u16(Endian::Big)?;
         let max_equals_min = cursor.read_u8()? != 0;
-        let sequences;
         let sequence = cursor.read_u24(Endian::Little)?;
-        if max_equals_min {
-            sequences = (sequence, sequence)
+        let sequences = if max_equals_min {
+            (sequence, sequence)
         } else {
             let sequence_max = cursor.read_u24(Endian::Little)?;
-            sequences = (sequence, sequence_max)
-        }
+            (sequence, sequence_max)
+        };
         Ok(Self {
             record_count,
             max_equals_min,
@@ -67,3 +66,13 @@ impl Packet for Nack {
         })
     }
 }
+
+impl fmt::Display for Nack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.max_equals_min {
+            write!(f, "NACK #{}", self.sequences.0)
+        } else {
+            write!(f, "NACK #{}..=#{}", self.sequences.0, self.sequences.1)
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result, net::SocketAddr};
+
+#[derive(Clone, Debug)]
+pub struct NatConnectAt {
+    pub guid: u64,
+    pub address: SocketAddr,
+    pub delay: u64,
+    pub initiator: bool,
+    pub token: u64,
+}
+
+impl NatConnectAt {
+    pub fn new(guid: u64, address: SocketAddr, delay: u64, initiator: bool, token: u64) -> Self {
+        Self {
+            guid,
+            address,
+            delay,
+            initiator,
+            token,
+        }
+    }
+}
+
+impl Packet for NatConnectAt {
+    const ID: u8 = 0x3c;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            guid: cursor.read_u64(Endian::Big)?,
+            address: cursor.read_address()?,
+            delay: cursor.read_u64(Endian::Big)?,
+            initiator: cursor.read_u8()? != 0,
+            token: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.guid, Endian::Big);
+        cursor.write_address(self.address);
+        cursor.write_u64(self.delay, Endian::Big);
+        cursor.write_u8(self.initiator as u8);
+        cursor.write_u64(self.token, Endian::Big);
+    }
+}
+
+impl fmt::Display for NatConnectAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NatConnectAt guid={:#x} address={} delay={} initiator={} token={:#x}",
+            self.guid, self.address, self.delay, self.initiator, self.token
+        )
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct NatPunch {
+    _magic: bool,
+    pub guid: u64,
+    pub token: u64,
+}
+
+impl NatPunch {
+    pub fn new(guid: u64, token: u64) -> Self {
+        Self {
+            _magic: true,
+            guid,
+            token,
+        }
+    }
+}
+
+impl Packet for NatPunch {
+    const ID: u8 = 0x3d;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            _magic: cursor.read_magic()?,
+            guid: cursor.read_u64(Endian::Big)?,
+            token: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_magic();
+        cursor.write_u64(self.guid, Endian::Big);
+        cursor.write_u64(self.token, Endian::Big);
+    }
+}
+
+impl fmt::Display for NatPunch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NatPunch guid={:#x} token={:#x}", self.guid, self.token)
+    }
+}
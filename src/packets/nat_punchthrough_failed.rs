@@ -0,0 +1,38 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct NatPunchthroughFailed {
+    pub target: u64,
+}
+
+impl NatPunchthroughFailed {
+    pub fn new(target: u64) -> Self {
+        Self { target }
+    }
+}
+
+impl Packet for NatPunchthroughFailed {
+    const ID: u8 = 0x3e;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            target: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.target, Endian::Big);
+    }
+}
+
+impl fmt::Display for NatPunchthroughFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NatPunchthroughFailed target={:#x}", self.target)
+    }
+}
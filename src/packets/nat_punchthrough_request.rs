@@ -0,0 +1,38 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct NatPunchthroughRequest {
+    pub target: u64,
+}
+
+impl NatPunchthroughRequest {
+    pub fn new(target: u64) -> Self {
+        Self { target }
+    }
+}
+
+impl Packet for NatPunchthroughRequest {
+    const ID: u8 = 0x37;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            target: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.target, Endian::Big);
+    }
+}
+
+impl fmt::Display for NatPunchthroughRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NatPunchthroughRequest target={:#x}", self.target)
+    }
+}
@@ -3,9 +3,9 @@ use bytes::BytesMut;
 use crate::packets::Packet;
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::{io::Result, net::SocketAddr};
+use std::{fmt, io::Result, net::SocketAddr};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct NewIncomingConnection {
     pub server_address: SocketAddr,
     pub request_timestamp: i64,
@@ -33,3 +33,13 @@ impl Packet for NewIncomingConnection {
         cursor.write_i64(self.accepted_timestamp, Endian::Big);
     }
 }
+
+impl fmt::Display for NewIncomingConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NewIncomingConnection server_address={} request_timestamp={} accepted_timestamp={}",
+            self.server_address, self.request_timestamp, self.accepted_timestamp
+        )
+    }
+}
@@ -3,9 +3,9 @@ use bytes::BytesMut;
 use crate::packets::Packet;
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct OpenConnectionReply1 {
     _magic: bool,
     pub guid: u64,
@@ -44,3 +44,13 @@ impl Packet for OpenConnectionReply1 {
         cursor.write_u16(self.mtu_size, Endian::Big);
     }
 }
+
+impl fmt::Display for OpenConnectionReply1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OpenConnectionReply1 guid={:#x} use_encryption={} mtu_size={}",
+            self.guid, self.use_encryption, self.mtu_size
+        )
+    }
+}
@@ -3,9 +3,9 @@ use bytes::BytesMut;
 use crate::packets::Packet;
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::{io::Result, net::SocketAddr};
+use std::{fmt, io::Result, net::SocketAddr};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct OpenConnectionReply2 {
     _magic: bool,
     pub guid: u64,
@@ -47,3 +47,13 @@ impl Packet for OpenConnectionReply2 {
         cursor.write_u8(self.encryption_enabled);
     }
 }
+
+impl fmt::Display for OpenConnectionReply2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OpenConnectionReply2 guid={:#x} address={} mtu={} encryption_enabled={}",
+            self.guid, self.address, self.mtu, self.encryption_enabled
+        )
+    }
+}
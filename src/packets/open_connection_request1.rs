@@ -3,9 +3,9 @@ use crate::reader::Reader;
 use crate::writer::Writer;
 use actix::prelude::*;
 use bytes::BytesMut;
-use std::{convert::TryInto, io::Result};
+use std::{convert::TryInto, fmt, io::Result};
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct OpenConnectionRequest1 {
     _magic: bool,
@@ -40,3 +40,13 @@ impl Packet for OpenConnectionRequest1 {
         cursor.write(vec![0; (self.mtu_size as usize) - (cursor.pos() + 32)].as_slice());
     }
 }
+
+impl fmt::Display for OpenConnectionRequest1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OpenConnectionRequest1 protocol_version={} mtu_size={}",
+            self.protocol_version, self.mtu_size
+        )
+    }
+}
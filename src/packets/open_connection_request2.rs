@@ -1,17 +1,18 @@
-use crate::packets::Packet;
+use crate::packets::{Packet, COOKIE_EXTENSION};
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
 use actix::prelude::*;
 use bytes::BytesMut;
-use std::{io::Result, net::SocketAddr};
+use std::{fmt, io::Result, net::SocketAddr};
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct OpenConnectionRequest2 {
     _magic: bool,
     pub address: SocketAddr,
     pub mtu: u16,
     pub guid: u64,
+    pub cookie: Option<u64>,
 }
 
 impl OpenConnectionRequest2 {
@@ -21,8 +22,13 @@ impl OpenConnectionRequest2 {
             address,
             mtu,
             guid,
+            cookie: None,
         }
     }
+    pub fn with_cookie(mut self, cookie: u64) -> Self {
+        self.cookie = Some(cookie);
+        self
+    }
 }
 
 impl Packet for OpenConnectionRequest2 {
@@ -34,6 +40,10 @@ impl Packet for OpenConnectionRequest2 {
             address: cursor.read_address()?,
             mtu: cursor.read_u16(Endian::Big)?,
             guid: cursor.read_u64(Endian::Big)?,
+            cookie: match (payload.len() as u64 - cursor.pos(), cursor.read_u8()) {
+                (9, Ok(COOKIE_EXTENSION)) => Some(cursor.read_u64(Endian::Big)?),
+                _ => None,
+            },
         })
     }
 
@@ -43,5 +53,23 @@ impl Packet for OpenConnectionRequest2 {
         cursor.write_address(self.address);
         cursor.write_u16(self.mtu, Endian::Big);
         cursor.write_u64(self.guid, Endian::Big);
+        if let Some(cookie) = self.cookie {
+            cursor.write_u8(COOKIE_EXTENSION);
+            cursor.write_u64(cookie, Endian::Big);
+        }
+    }
+}
+
+impl fmt::Display for OpenConnectionRequest2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OpenConnectionRequest2 address={} mtu={} guid={:#x}",
+            self.address, self.mtu, self.guid
+        )?;
+        if let Some(cookie) = self.cookie {
+            write!(f, " cookie={:#x}", cookie)?;
+        }
+        Ok(())
     }
 }
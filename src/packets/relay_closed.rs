@@ -0,0 +1,38 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct RelayClosed {
+    pub guid: u64,
+}
+
+impl RelayClosed {
+    pub fn new(guid: u64) -> Self {
+        Self { guid }
+    }
+}
+
+impl Packet for RelayClosed {
+    const ID: u8 = 0x48;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            guid: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.guid, Endian::Big);
+    }
+}
+
+impl fmt::Display for RelayClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RelayClosed guid={:#x}", self.guid)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::{Bytes, BytesMut};
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct RelayData {
+    pub guid: u64,
+    pub payload: Bytes,
+}
+
+impl RelayData {
+    pub fn new(guid: u64, payload: Bytes) -> Self {
+        Self { guid, payload }
+    }
+}
+
+impl Packet for RelayData {
+    const ID: u8 = 0x47;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        let guid = cursor.read_u64(Endian::Big)?;
+        Ok(Self {
+            guid,
+            payload: Bytes::copy_from_slice(&payload[cursor.pos() as usize..]),
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.guid, Endian::Big);
+        cursor.write(&self.payload);
+    }
+}
+
+impl fmt::Display for RelayData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RelayData guid={:#x} length={}",
+            self.guid,
+            self.payload.len()
+        )
+    }
+}
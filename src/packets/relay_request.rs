@@ -0,0 +1,45 @@
+use crate::{
+    packets::Packet,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+use bytes::BytesMut;
+use std::{fmt, io::Result};
+
+#[derive(Clone, Debug)]
+pub struct RelayRequest {
+    pub target: u64,
+    pub token: u64,
+}
+
+impl RelayRequest {
+    pub fn new(target: u64, token: u64) -> Self {
+        Self { target, token }
+    }
+}
+
+impl Packet for RelayRequest {
+    const ID: u8 = 0x45;
+    fn read(payload: &[u8]) -> Result<Self> {
+        let mut cursor = Reader::new(payload);
+        Ok(Self {
+            target: cursor.read_u64(Endian::Big)?,
+            token: cursor.read_u64(Endian::Big)?,
+        })
+    }
+    fn write(&self, bytes: &mut BytesMut) {
+        let mut cursor = Writer::new(bytes);
+        cursor.write_u64(self.target, Endian::Big);
+        cursor.write_u64(self.token, Endian::Big);
+    }
+}
+
+impl fmt::Display for RelayRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RelayRequest target={:#x} token={:#x}",
+            self.target, self.token
+        )
+    }
+}
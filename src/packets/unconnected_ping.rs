@@ -3,9 +3,9 @@ use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
 use actix::prelude::*;
 use bytes::BytesMut;
-use std::io::Result;
+use std::{fmt, io::Result};
 
-#[derive(Clone, Message)]
+#[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
 pub struct UnconnectedPing {
     pub time: i64,
@@ -40,3 +40,13 @@ impl Packet for UnconnectedPing {
         cursor.write_u64(self.guid, Endian::Big);
     }
 }
+
+impl fmt::Display for UnconnectedPing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UnconnectedPing time={} guid={:#x}",
+            self.time, self.guid
+        )
+    }
+}
@@ -2,11 +2,11 @@ use bytes::BytesMut;
 
 use crate::reader::{Endian, Reader};
 use crate::writer::Writer;
-use std::io::Result;
+use std::{fmt, io::Result};
 
 use crate::packets::Packet;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct UnconnectedPong {
     pub time: i64,
     pub guid: u64,
@@ -44,3 +44,13 @@ impl Packet for UnconnectedPong {
         cursor.write_string(&self.motd);
     }
 }
+
+impl fmt::Display for UnconnectedPong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UnconnectedPong time={} guid={:#x} motd={:?}",
+            self.time, self.guid, self.motd
+        )
+    }
+}
@@ -0,0 +1,149 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use actix::{dev::ToEnvelope, prelude::*};
+
+use crate::{
+    client::{ClientHandle, RakClientEvent},
+    endpoint::{ClientEndpoint, EndpointHandle, Inbound},
+    metrics::ServerMetrics,
+    punch::{NatPunchthrough, PunchEvent, PunchHandle},
+    relay::{RelayClient, RelayEvent, RelayHandle},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+    udp::{Transport, UdpActor},
+};
+
+pub struct RakPeer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    server: Addr<RakServer<T>>,
+    endpoint: EndpointHandle,
+    guid: u64,
+}
+
+impl<T> RakPeer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    pub fn new(
+        socket: tokio::net::UdpSocket,
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+    ) -> Self {
+        Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            move |router, udp_worker, metrics| {
+                Transport::new(UdpActor::new(socket, router, udp_worker, Some(metrics)))
+            },
+        )
+    }
+
+    pub fn new_in_memory(
+        network: &MemoryNetwork,
+        local: SocketAddr,
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+    ) -> Self {
+        let network = network.clone();
+        Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            move |router, udp_worker, metrics| {
+                Transport::new(network.bind(local, router.recipient(), udp_worker, Some(metrics)))
+            },
+        )
+    }
+
+    fn start<F>(guid: u64, motd: String, handler: Addr<T>, thread: u32, transport: F) -> Self
+    where
+        F: FnOnce(Addr<ClientEndpoint>, &Arbiter, Arc<ServerMetrics>) -> Transport + Send + 'static,
+    {
+        let mut endpoint = None;
+        let server = RakServer::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            0,
+            |addr, _arbiters, routes, metrics| {
+                let inbound = Inbound {
+                    server: addr.recipient(),
+                    routes: routes.clone(),
+                    guid,
+                };
+                let handle = ClientEndpoint::start(
+                    System::current().arbiter(),
+                    Some(inbound),
+                    move |router, udp_worker| transport(router, udp_worker, metrics),
+                );
+                let udp = handle.transport();
+                endpoint = Some(handle);
+                vec![udp]
+            },
+        );
+        Self {
+            server,
+            endpoint: endpoint.unwrap(),
+            guid,
+        }
+    }
+
+    pub fn server(&self) -> &Addr<RakServer<T>> {
+        &self.server
+    }
+
+    pub fn client<C>(&self, handler: Addr<C>, arbiter: &Arbiter) -> ClientHandle
+    where
+        C: Actor,
+        C: Handler<RakClientEvent>,
+        <C as actix::Actor>::Context: ToEnvelope<C, RakClientEvent>,
+    {
+        self.endpoint.client(self.guid, handler, arbiter)
+    }
+
+    pub fn connect<C>(
+        &self,
+        address: SocketAddr,
+        handler: Addr<C>,
+        arbiter: &Arbiter,
+    ) -> ClientHandle
+    where
+        C: Actor,
+        C: Handler<RakClientEvent>,
+        <C as actix::Actor>::Context: ToEnvelope<C, RakClientEvent>,
+    {
+        let client = self.client(handler, arbiter);
+        client.connect(address);
+        client
+    }
+
+    pub fn punchthrough(
+        &self,
+        facilitator: SocketAddr,
+        events: Recipient<PunchEvent>,
+    ) -> PunchHandle {
+        NatPunchthrough::start(self.endpoint.clone(), self.guid, facilitator, events)
+    }
+
+    pub fn relay(&self, relay: SocketAddr, events: Recipient<RelayEvent>) -> RelayHandle {
+        RelayClient::start(self.endpoint.clone(), self.guid, relay, events)
+    }
+
+    pub fn connections(&self) -> usize {
+        self.endpoint.connections()
+    }
+}
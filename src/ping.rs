@@ -35,7 +35,7 @@ where
     pub fn new(socket: tokio::net::UdpSocket, handler: Addr<T>) -> Addr<Self> {
         let udp_worker = Arbiter::new();
         Self::create(|ctx| Self {
-            udp: UdpActor::new(socket, ctx.address(), &udp_worker),
+            udp: UdpActor::new(socket, ctx.address(), &udp_worker, None),
             handler,
             udp_worker,
         })
@@ -78,7 +78,7 @@ where
         let ping = UnconnectedPing::new(time() as i64, 0x0);
         let payload = encode(ping);
         self.udp.do_send(SendUdp(UdpPacket {
-            bytes: payload,
+            bytes: payload.freeze(),
             addr: msg.0,
         }));
     }
@@ -0,0 +1,418 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use actix::prelude::*;
+
+use crate::{
+    client::{ClientHandle, RakClientEvent},
+    endpoint::EndpointHandle,
+    macros::{debug, unwrap_or_return},
+    packets::{
+        decode, encode, NatConnectAt, NatPunch, NatPunchthroughFailed, NatPunchthroughRequest,
+        Packet,
+    },
+    relay::{RelayClient, RelayEvent, RelayGrant, RelayHandle},
+    server::{ConnectionHandle, RakServerEvent, RakServerNotice},
+    session::random,
+    udp::{ReceivedUdp, Transport},
+};
+
+const PUNCH_DELAY: Duration = Duration::from_millis(100);
+
+const PUNCH_INTERVAL: Duration = Duration::from_millis(50);
+
+const PUNCH_ATTEMPTS: u32 = 40;
+
+#[derive(Default)]
+pub struct NatFacilitator {
+    peers: HashMap<u64, ConnectionHandle>,
+    relay: Option<Recipient<RelayGrant>>,
+}
+
+impl NatFacilitator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_relay(mut self, relay: Recipient<RelayGrant>) -> Self {
+        self.relay = Some(relay);
+        self
+    }
+
+    fn request(&self, from: ConnectionHandle, request: NatPunchthroughRequest) {
+        let target = match self.peers.get(&request.target) {
+            Some(target) if target.guid != from.guid => target,
+            _ => {
+                from.send(encode(NatPunchthroughFailed::new(request.target)));
+                return;
+            }
+        };
+        debug!(
+            from = from.guid,
+            to = target.guid,
+            "coordinating punchthrough"
+        );
+        let delay = PUNCH_DELAY.as_millis() as u64;
+        let token = random();
+        if let Some(relay) = &self.relay {
+            let _ = relay.do_send(RelayGrant {
+                peers: (from.guid, target.guid),
+                token,
+            });
+        }
+        from.send(encode(NatConnectAt::new(
+            target.guid,
+            target.address,
+            delay,
+            true,
+            token,
+        )));
+        target.send(encode(NatConnectAt::new(
+            from.guid,
+            from.address,
+            delay,
+            false,
+            token,
+        )));
+    }
+}
+
+impl Actor for NatFacilitator {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for NatFacilitator {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Connected(handle) => {
+                self.peers.insert(handle.guid, handle);
+            }
+            RakServerEvent::Packet(handle, bytes) => {
+                if bytes.first() == Some(&NatPunchthroughRequest::ID) {
+                    let request = unwrap_or_return!(decode::<NatPunchthroughRequest>(&bytes));
+                    self.request(handle, request);
+                }
+            }
+            RakServerEvent::Disconnected(address, guid) => {
+                if self.peers.get(&guid).map(|x| x.address) == Some(address) {
+                    self.peers.remove(&guid);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<RakServerNotice> for NatFacilitator {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerNotice, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerNotice::Migrated(handle, _) = msg {
+            self.peers.insert(handle.guid, handle);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PunchFailure {
+    UnknownPeer,
+    Timeout,
+    FacilitatorUnreachable,
+    RelayClosed,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub enum PunchEvent {
+    // The hole is open but no session exists yet: the initiator connects to
+    // `address` through its RakPeer and the other side accepts it there.
+    Ready {
+        guid: u64,
+        address: SocketAddr,
+        initiator: bool,
+    },
+    Failed(u64, PunchFailure),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Facilitator {
+    Connecting,
+    Connected,
+    Lost,
+}
+
+struct Attempt {
+    address: SocketAddr,
+    initiator: bool,
+    token: u64,
+    remaining: u32,
+    handle: Option<SpawnHandle>,
+}
+
+pub struct NatPunchthrough {
+    guid: u64,
+    endpoint: EndpointHandle,
+    udp: Transport,
+    client: Option<ClientHandle>,
+    facilitator: Facilitator,
+    queued: Vec<u64>,
+    attempts: HashMap<u64, Attempt>,
+    relay: Option<RelayHandle>,
+    relaying: HashMap<u64, bool>,
+    events: Recipient<PunchEvent>,
+}
+
+impl NatPunchthrough {
+    pub(crate) fn start(
+        endpoint: EndpointHandle,
+        guid: u64,
+        facilitator: SocketAddr,
+        events: Recipient<PunchEvent>,
+    ) -> PunchHandle {
+        let addr = Self::create(move |ctx| {
+            let client = endpoint.client(guid, ctx.address(), System::current().arbiter());
+            client.connect(facilitator);
+            endpoint.set_punch(Some(ctx.address().recipient()));
+            Self {
+                guid,
+                udp: endpoint.transport(),
+                endpoint,
+                client: Some(client),
+                facilitator: Facilitator::Connecting,
+                queued: Vec::new(),
+                attempts: HashMap::new(),
+                relay: None,
+                relaying: HashMap::new(),
+                events,
+            }
+        });
+        PunchHandle { addr }
+    }
+
+    fn request(&mut self, target: u64) {
+        match self.facilitator {
+            Facilitator::Connecting => self.queued.push(target),
+            Facilitator::Connected => {
+                if let Some(client) = &self.client {
+                    client.packet(encode(NatPunchthroughRequest::new(target)));
+                }
+            }
+            Facilitator::Lost => self.failed(target, PunchFailure::FacilitatorUnreachable),
+        }
+    }
+
+    fn failed(&self, guid: u64, reason: PunchFailure) {
+        let _ = self.events.do_send(PunchEvent::Failed(guid, reason));
+    }
+
+    fn schedule(&mut self, connect: NatConnectAt, ctx: &mut Context<Self>) {
+        if self.attempts.contains_key(&connect.guid) {
+            return;
+        }
+        let guid = connect.guid;
+        let handle = ctx.run_later(Duration::from_millis(connect.delay), move |act, ctx| {
+            act.punch(guid);
+            let handle = ctx.run_interval(PUNCH_INTERVAL, move |act, ctx| act.tick(guid, ctx));
+            if let Some(attempt) = act.attempts.get_mut(&guid) {
+                attempt.handle = Some(handle);
+            }
+        });
+        self.attempts.insert(
+            guid,
+            Attempt {
+                address: connect.address,
+                initiator: connect.initiator,
+                token: connect.token,
+                remaining: PUNCH_ATTEMPTS,
+                handle: Some(handle),
+            },
+        );
+    }
+
+    fn punch(&self, guid: u64) {
+        if let Some(attempt) = self.attempts.get(&guid) {
+            self.udp.send_to(
+                encode(NatPunch::new(self.guid, attempt.token)),
+                attempt.address,
+            );
+        }
+    }
+
+    fn tick(&mut self, guid: u64, ctx: &mut Context<Self>) {
+        let attempt = match self.attempts.get_mut(&guid) {
+            Some(attempt) => attempt,
+            None => return,
+        };
+        attempt.remaining = attempt.remaining.saturating_sub(1);
+        if attempt.remaining > 0 {
+            self.punch(guid);
+            return;
+        }
+        if let Some(handle) = attempt.handle.take() {
+            ctx.cancel_future(handle);
+        }
+        let (initiator, token) = (attempt.initiator, attempt.token);
+        self.attempts.remove(&guid);
+        match &self.relay {
+            Some(relay) => {
+                debug!(guid, "punchthrough timed out, falling back to relay");
+                self.relaying.insert(guid, initiator);
+                relay.open(guid, token);
+            }
+            None => self.failed(guid, PunchFailure::Timeout),
+        }
+    }
+
+    fn lost(&mut self) {
+        self.facilitator = Facilitator::Lost;
+        for target in std::mem::take(&mut self.queued) {
+            self.failed(target, PunchFailure::FacilitatorUnreachable);
+        }
+    }
+}
+
+impl Actor for NatPunchthrough {
+    type Context = Context<Self>;
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.endpoint.set_punch(None);
+    }
+}
+
+impl Handler<RakClientEvent> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                self.facilitator = Facilitator::Connected;
+                for target in std::mem::take(&mut self.queued) {
+                    self.request(target);
+                }
+            }
+            RakClientEvent::Packet(bytes) => match bytes.first().copied() {
+                Some(NatConnectAt::ID) => {
+                    let connect = unwrap_or_return!(decode::<NatConnectAt>(&bytes));
+                    self.schedule(connect, ctx);
+                }
+                Some(NatPunchthroughFailed::ID) => {
+                    let failed = unwrap_or_return!(decode::<NatPunchthroughFailed>(&bytes));
+                    self.failed(failed.target, PunchFailure::UnknownPeer);
+                }
+                _ => {}
+            },
+            RakClientEvent::ConnectionFailed(_) | RakClientEvent::Disconnected => self.lost(),
+        }
+    }
+}
+
+impl Handler<ReceivedUdp> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, msg: ReceivedUdp, ctx: &mut Self::Context) -> Self::Result {
+        let punch = unwrap_or_return!(decode::<NatPunch>(&msg.0.bytes));
+        let address = msg.0.addr;
+        let expected = self.attempts.get(&punch.guid).is_some_and(|attempt| {
+            attempt.token == punch.token && attempt.address.ip() == address.ip()
+        });
+        if !expected {
+            return;
+        }
+        if let Some(mut attempt) = self.attempts.remove(&punch.guid) {
+            if let Some(handle) = attempt.handle.take() {
+                ctx.cancel_future(handle);
+            }
+            debug!(guid = punch.guid, %address, "punched through");
+            self.udp
+                .send_to(encode(NatPunch::new(self.guid, attempt.token)), address);
+            let _ = self.events.do_send(PunchEvent::Ready {
+                guid: punch.guid,
+                address,
+                initiator: attempt.initiator,
+            });
+        }
+    }
+}
+
+impl Handler<RelayEvent> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, msg: RelayEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RelayEvent::Ready { guid, address } => {
+                if let Some(initiator) = self.relaying.remove(&guid) {
+                    let _ = self.events.do_send(PunchEvent::Ready {
+                        guid,
+                        address,
+                        initiator,
+                    });
+                }
+            }
+            RelayEvent::Closed(guid) => {
+                if self.relaying.remove(&guid).is_some() {
+                    self.failed(guid, PunchFailure::RelayClosed);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<Fallback> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, msg: Fallback, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(relay) = self.relay.take() {
+            relay.shutdown();
+        }
+        self.relay = msg.0.map(|relay| {
+            RelayClient::start(
+                self.endpoint.clone(),
+                self.guid,
+                relay,
+                ctx.address().recipient(),
+            )
+        });
+    }
+}
+
+impl Handler<Punch> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, msg: Punch, _ctx: &mut Self::Context) -> Self::Result {
+        self.request(msg.0);
+    }
+}
+
+impl Handler<Close> for NatPunchthrough {
+    type Result = ();
+    fn handle(&mut self, _msg: Close, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(client) = self.client.take() {
+            client.disconnect();
+        }
+        if let Some(relay) = self.relay.take() {
+            relay.shutdown();
+        }
+        ctx.stop();
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Punch(u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Close;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Fallback(Option<SocketAddr>);
+
+#[derive(Clone)]
+pub struct PunchHandle {
+    addr: Addr<NatPunchthrough>,
+}
+
+impl PunchHandle {
+    pub fn punch(&self, guid: u64) {
+        self.addr.do_send(Punch(guid));
+    }
+    pub fn fallback(&self, relay: Option<SocketAddr>) {
+        self.addr.do_send(Fallback(relay));
+    }
+    pub fn close(&self) {
+        self.addr.do_send(Close);
+    }
+}
@@ -70,10 +70,7 @@ impl<T: Read + AsRef<[u8]>> Reader<T> {
         self.read(&mut strbuf)?;
         match String::from_utf8(strbuf) {
             Ok(p) => Ok(p),
-            Err(e) => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                e.to_string(),
-            )),
+            Err(e) => Err(std::io::Error::other(e.to_string())),
         }
     }
     pub fn read_magic(&mut self) -> Result<bool> {
@@ -95,7 +92,7 @@ impl<T: Read + AsRef<[u8]>> Reader<T> {
             Ok(SocketAddr::new(IpAddr::V4(ip), port))
         } else {
             self.next(2);
-            let port = self.read_u16(Endian::Little)?;
+            let port = self.read_u16(Endian::Big)?;
             self.next(4);
             let mut addr_buf = [0; 16];
             self.cursor.read_exact(&mut addr_buf)?;
@@ -1,34 +1,71 @@
 use std::collections::HashMap;
 
-use crate::{packet::SplitPacketQueue, packets::frame::Frame};
+use crate::{
+    packet::SplitPacketQueue,
+    packets::{frame::Frame, ORDER_CHANNELS},
+};
 
 pub struct ReceivedQueue {
-    min: u32,
-    max: u32,
-    packet_queue: HashMap<u32, Frame>,
+    channels: Vec<OrderingChannel>,
     splits: SplitPacketQueue,
 }
 impl ReceivedQueue {
     pub fn new() -> Self {
         Self {
-            min: 0,
-            max: 0,
-            packet_queue: HashMap::new(),
+            channels: (0..ORDER_CHANNELS)
+                .map(|_| OrderingChannel::new())
+                .collect(),
             splits: SplitPacketQueue::new(),
         }
     }
-    pub fn add(&mut self, frame: Frame) {
-        if frame.split {
-            self.splits.add(frame);
-            for mut packet in self.splits.get_and_clear() {
-                let f = packet.get_frame().unwrap();
-                if f.order_index >= self.max {
-                    self.max = f.order_index + 1
-                }
-                self.packet_queue.insert(f.order_index, f);
+    pub fn add(&mut self, frame: Frame) -> Vec<Frame> {
+        if !frame.split {
+            return self.order(frame);
+        }
+        self.splits.add(frame);
+        let mut ret = vec![];
+        for mut packet in self.splits.get_and_clear() {
+            let f = packet.get_frame().unwrap();
+            ret.append(&mut self.order(f));
+        }
+        ret
+    }
+    fn order(&mut self, frame: Frame) -> Vec<Frame> {
+        if !frame.reliability.sequenced_or_ordered() {
+            return vec![frame];
+        }
+        let channel = match self.channels.get_mut(frame.order_channel as usize) {
+            Some(channel) => channel,
+            None => return vec![],
+        };
+        if frame.reliability.sequenced() {
+            if frame.order_index < channel.min || frame.sequence_index < channel.sequence {
+                return vec![];
             }
-            return;
+            channel.sequence = frame.sequence_index + 1;
+            return vec![frame];
         }
+        channel.add(frame);
+        channel.get_all()
+    }
+}
+
+struct OrderingChannel {
+    min: u32,
+    max: u32,
+    sequence: u32,
+    packet_queue: HashMap<u32, Frame>,
+}
+impl OrderingChannel {
+    fn new() -> Self {
+        Self {
+            min: 0,
+            max: 0,
+            sequence: 0,
+            packet_queue: HashMap::new(),
+        }
+    }
+    fn add(&mut self, frame: Frame) {
         if frame.order_index < self.min {
             return;
         }
@@ -40,16 +77,14 @@ impl ReceivedQueue {
         }
         self.packet_queue.insert(frame.order_index, frame);
     }
-    pub fn get_all(&mut self) -> Vec<Frame> {
+    fn get_all(&mut self) -> Vec<Frame> {
         let mut ret = vec![];
         let mut index = self.min;
         for o in self.min..self.max {
-            if self.packet_queue.contains_key(&o) {
-                ret.push(self.packet_queue.get(&o).unwrap().clone());
-            } else {
-                break;
+            match self.packet_queue.remove(&o) {
+                Some(frame) => ret.push(frame),
+                None => break,
             }
-            self.packet_queue.remove(&o);
             index += 1;
         }
         self.min = index;
@@ -0,0 +1,526 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use actix::prelude::*;
+
+use crate::{
+    client::{ClientHandle, RakClientEvent},
+    endpoint::EndpointHandle,
+    macros::{debug, unwrap_or_return},
+    pacing::{admit, TokenBucket},
+    packets::{
+        decode, encode, Packet, RelayClosed, RelayData, RelayReady, RelayRequest, Reliability,
+    },
+    send::{RateLimit, SendOptions},
+    server::{ConnectionHandle, RakServerEvent, RakServerNotice},
+    udp::{SendUdp, UdpPacket},
+};
+
+const RELAY_PREFIX: [u16; 4] = [0xfd72, 0x656c, 0x6179, 0];
+
+const RELAY_PORT: u16 = 19132;
+
+const DEFAULT_IDLE: Duration = Duration::from_secs(30);
+
+const MIN_IDLE: Duration = Duration::from_millis(100);
+
+const MAX_PENDING: usize = 16;
+
+// A relayed datagram has to fit one unsplit frame of the outer session, which
+// would otherwise split it and send it reliably. The smallest outer MTU is 584
+// and leaves 584 - 47 bytes for a RelayData, 9 of them its header, while a
+// session never sends a datagram longer than its MTU - 30.
+pub(crate) const RELAY_MTU: u16 = 584 - 47 - 9 + 30;
+
+pub fn relay_address(guid: u64) -> SocketAddr {
+    let ip = Ipv6Addr::new(
+        RELAY_PREFIX[0],
+        RELAY_PREFIX[1],
+        RELAY_PREFIX[2],
+        RELAY_PREFIX[3],
+        (guid >> 48) as u16,
+        (guid >> 32) as u16,
+        (guid >> 16) as u16,
+        guid as u16,
+    );
+    SocketAddr::new(IpAddr::V6(ip), RELAY_PORT)
+}
+
+pub fn relay_guid(address: &SocketAddr) -> Option<u64> {
+    match address.ip() {
+        IpAddr::V6(ip) if ip.segments()[..4] == RELAY_PREFIX && address.port() == RELAY_PORT => {
+            Some(
+                ip.segments()[4..]
+                    .iter()
+                    .fold(0, |guid, x| guid << 16 | *x as u64),
+            )
+        }
+        _ => None,
+    }
+}
+
+fn pair(a: u64, b: u64) -> (u64, u64) {
+    (a.min(b), a.max(b))
+}
+
+fn unreliable() -> SendOptions {
+    SendOptions::new().reliability(Reliability::Unreliable)
+}
+
+struct Relay {
+    bucket: Option<TokenBucket>,
+    last: Instant,
+    forwarded: u64,
+    dropped: u64,
+}
+
+pub struct RelayServer {
+    bandwidth: Option<RateLimit>,
+    idle: Duration,
+    peers: HashMap<u64, ConnectionHandle>,
+    grants: HashMap<(u64, u64), (u64, Instant)>,
+    requests: HashMap<(u64, u64), Instant>,
+    relays: HashMap<(u64, u64), Relay>,
+}
+
+impl RelayServer {
+    pub fn new(bandwidth: Option<RateLimit>, idle: Duration) -> Self {
+        Self {
+            bandwidth,
+            idle: idle.max(MIN_IDLE),
+            peers: HashMap::new(),
+            grants: HashMap::new(),
+            requests: HashMap::new(),
+            relays: HashMap::new(),
+        }
+    }
+
+    fn notify(&self, guid: u64, closed: u64) {
+        if let Some(peer) = self.peers.get(&guid) {
+            peer.send(encode(RelayClosed::new(closed)));
+        }
+    }
+
+    fn request(&mut self, from: ConnectionHandle, request: RelayRequest) {
+        let target = request.target;
+        let granted = self.grants.get(&pair(from.guid, target)).map(|x| x.0);
+        if target == from.guid || granted != Some(request.token) {
+            debug!(
+                from = from.guid,
+                to = target,
+                "relay request without a grant"
+            );
+            from.send(encode(RelayClosed::new(target)));
+            return;
+        }
+        if let Some(relay) = self.relays.get_mut(&pair(from.guid, target)) {
+            relay.last = Instant::now();
+            from.send(encode(RelayReady::new(target)));
+            return;
+        }
+        let peer = match self.peers.get(&target) {
+            Some(peer) if self.requests.remove(&(target, from.guid)).is_some() => peer,
+            _ => {
+                let pending = self.requests.keys().filter(|x| x.0 == from.guid).count();
+                if pending >= MAX_PENDING {
+                    from.send(encode(RelayClosed::new(target)));
+                } else {
+                    self.requests.insert((from.guid, target), Instant::now());
+                }
+                return;
+            }
+        };
+        debug!(a = from.guid, b = target, "relay opened");
+        from.send(encode(RelayReady::new(target)));
+        peer.send(encode(RelayReady::new(from.guid)));
+        self.relays.insert(
+            pair(from.guid, target),
+            Relay {
+                bucket: self.bandwidth.map(TokenBucket::new),
+                last: Instant::now(),
+                forwarded: 0,
+                dropped: 0,
+            },
+        );
+    }
+
+    fn forward(&mut self, from: &ConnectionHandle, data: RelayData) {
+        let relay = match self.relays.get_mut(&pair(from.guid, data.guid)) {
+            Some(relay) => relay,
+            None => return,
+        };
+        let target = match self.peers.get(&data.guid) {
+            Some(target) => target,
+            None => return,
+        };
+        if !admit(&mut relay.bucket, None, data.payload.len()) {
+            relay.dropped += 1;
+            return;
+        }
+        relay.forwarded += data.payload.len() as u64;
+        relay.last = Instant::now();
+        target.send_with(
+            encode(RelayData::new(from.guid, data.payload)).freeze(),
+            unreliable(),
+        );
+    }
+
+    fn close(&mut self, from: u64, target: u64) {
+        self.requests.remove(&(from, target));
+        if self.relays.remove(&pair(from, target)).is_some() {
+            self.notify(target, from);
+        }
+    }
+
+    fn left(&mut self, guid: u64) {
+        self.requests.retain(|(from, _), _| *from != guid);
+        let closed: Vec<_> = self
+            .relays
+            .keys()
+            .filter(|(a, b)| *a == guid || *b == guid)
+            .copied()
+            .collect();
+        for (a, b) in closed {
+            self.relays.remove(&(a, b));
+            self.notify(if a == guid { b } else { a }, guid);
+        }
+    }
+
+    fn expire(&mut self) {
+        let idle = self.idle;
+        let relays = &self.relays;
+        self.grants
+            .retain(|key, (_, since)| since.elapsed() < idle || relays.contains_key(key));
+        let requests: Vec<_> = self
+            .requests
+            .iter()
+            .filter(|(_, since)| since.elapsed() >= idle)
+            .map(|(key, _)| *key)
+            .collect();
+        for (from, target) in requests {
+            self.requests.remove(&(from, target));
+            self.notify(from, target);
+        }
+        let relays: Vec<_> = self
+            .relays
+            .iter()
+            .filter(|(_, relay)| relay.last.elapsed() >= idle)
+            .map(|(key, _)| *key)
+            .collect();
+        for (a, b) in relays {
+            debug!(a, b, "relay expired");
+            self.relays.remove(&(a, b));
+            self.notify(a, b);
+            self.notify(b, a);
+        }
+    }
+}
+
+impl Default for RelayServer {
+    fn default() -> Self {
+        Self::new(None, DEFAULT_IDLE)
+    }
+}
+
+impl Actor for RelayServer {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.idle / 4, |act, _ctx| act.expire());
+    }
+}
+
+impl Handler<RakServerEvent> for RelayServer {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Connected(handle) => {
+                self.peers.insert(handle.guid, handle);
+            }
+            RakServerEvent::Packet(handle, bytes) => match bytes.first().copied() {
+                Some(RelayRequest::ID) => {
+                    let request = unwrap_or_return!(decode::<RelayRequest>(&bytes));
+                    self.request(handle, request);
+                }
+                Some(RelayData::ID) => {
+                    let data = unwrap_or_return!(decode::<RelayData>(&bytes));
+                    self.forward(&handle, data);
+                }
+                Some(RelayClosed::ID) => {
+                    let closed = unwrap_or_return!(decode::<RelayClosed>(&bytes));
+                    self.close(handle.guid, closed.guid);
+                }
+                _ => {}
+            },
+            RakServerEvent::Disconnected(address, guid) => {
+                if self.peers.get(&guid).map(|x| x.address) == Some(address) {
+                    self.peers.remove(&guid);
+                    self.left(guid);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<RakServerNotice> for RelayServer {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerNotice, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerNotice::Migrated(handle, _) = msg {
+            self.peers.insert(handle.guid, handle);
+        }
+    }
+}
+
+impl Handler<RelayGrant> for RelayServer {
+    type Result = ();
+    fn handle(&mut self, msg: RelayGrant, _ctx: &mut Self::Context) -> Self::Result {
+        let (a, b) = msg.peers;
+        self.grants.insert(pair(a, b), (msg.token, Instant::now()));
+    }
+}
+
+impl Handler<ListRelays> for RelayServer {
+    type Result = MessageResult<ListRelays>;
+    fn handle(&mut self, _msg: ListRelays, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.relays
+                .iter()
+                .map(|(peers, relay)| RelayInfo {
+                    peers: *peers,
+                    forwarded: relay.forwarded,
+                    dropped: relay.dropped,
+                    idle: relay.last.elapsed(),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RelayInfo {
+    pub peers: (u64, u64),
+    pub forwarded: u64,
+    pub dropped: u64,
+    pub idle: Duration,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<RelayInfo>")]
+pub struct ListRelays;
+
+#[derive(Clone, Debug, Message)]
+#[rtype(result = "()")]
+pub struct RelayGrant {
+    pub peers: (u64, u64),
+    pub token: u64,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub enum RelayEvent {
+    Ready { guid: u64, address: SocketAddr },
+    Closed(u64),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Upstream {
+    Connecting,
+    Connected,
+    Lost,
+}
+
+pub struct RelayClient {
+    endpoint: EndpointHandle,
+    client: Option<ClientHandle>,
+    upstream: Upstream,
+    queued: Vec<(u64, u64)>,
+    pending: HashSet<u64>,
+    open: HashSet<u64>,
+    events: Recipient<RelayEvent>,
+}
+
+impl RelayClient {
+    pub(crate) fn start(
+        endpoint: EndpointHandle,
+        guid: u64,
+        relay: SocketAddr,
+        events: Recipient<RelayEvent>,
+    ) -> RelayHandle {
+        let addr = Self::create(move |ctx| {
+            let client = endpoint.client(guid, ctx.address(), System::current().arbiter());
+            client.connect(relay);
+            endpoint.set_relay(Some(ctx.address().recipient()));
+            Self {
+                endpoint,
+                client: Some(client),
+                upstream: Upstream::Connecting,
+                queued: Vec::new(),
+                pending: HashSet::new(),
+                open: HashSet::new(),
+                events,
+            }
+        });
+        RelayHandle { addr }
+    }
+
+    fn send<P: Packet>(&self, packet: P) {
+        if let Some(client) = &self.client {
+            client.packet(encode(packet));
+        }
+    }
+
+    fn request(&mut self, target: u64, token: u64) {
+        if self.open.contains(&target) {
+            let _ = self.events.do_send(RelayEvent::Ready {
+                guid: target,
+                address: relay_address(target),
+            });
+            return;
+        }
+        match self.upstream {
+            Upstream::Connecting => self.queued.push((target, token)),
+            Upstream::Connected => {
+                self.pending.insert(target);
+                self.send(RelayRequest::new(target, token));
+            }
+            Upstream::Lost => {
+                let _ = self.events.do_send(RelayEvent::Closed(target));
+            }
+        }
+    }
+
+    fn lost(&mut self) {
+        self.upstream = Upstream::Lost;
+        let queued = std::mem::take(&mut self.queued);
+        let pending = std::mem::take(&mut self.pending);
+        let open = std::mem::take(&mut self.open);
+        let queued = queued.into_iter().map(|x| x.0);
+        for guid in queued.chain(pending).chain(open) {
+            let _ = self.events.do_send(RelayEvent::Closed(guid));
+        }
+    }
+}
+
+impl Actor for RelayClient {
+    type Context = Context<Self>;
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.endpoint.set_relay(None);
+    }
+}
+
+impl Handler<RakClientEvent> for RelayClient {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                self.upstream = Upstream::Connected;
+                for (target, token) in std::mem::take(&mut self.queued) {
+                    self.request(target, token);
+                }
+            }
+            RakClientEvent::Packet(bytes) => match bytes.first().copied() {
+                Some(RelayData::ID) => {
+                    let data = unwrap_or_return!(decode::<RelayData>(&bytes));
+                    if self.open.contains(&data.guid) {
+                        self.endpoint.received(UdpPacket {
+                            bytes: data.payload,
+                            addr: relay_address(data.guid),
+                        });
+                    }
+                }
+                Some(RelayReady::ID) => {
+                    let ready = unwrap_or_return!(decode::<RelayReady>(&bytes));
+                    self.pending.remove(&ready.guid);
+                    if self.open.insert(ready.guid) {
+                        let _ = self.events.do_send(RelayEvent::Ready {
+                            guid: ready.guid,
+                            address: relay_address(ready.guid),
+                        });
+                    }
+                }
+                Some(RelayClosed::ID) => {
+                    let closed = unwrap_or_return!(decode::<RelayClosed>(&bytes));
+                    if self.pending.remove(&closed.guid) | self.open.remove(&closed.guid) {
+                        let _ = self.events.do_send(RelayEvent::Closed(closed.guid));
+                    }
+                }
+                _ => {}
+            },
+            RakClientEvent::ConnectionFailed(_) | RakClientEvent::Disconnected => self.lost(),
+        }
+    }
+}
+
+impl Handler<SendUdp> for RelayClient {
+    type Result = ();
+    fn handle(&mut self, msg: SendUdp, _ctx: &mut Self::Context) -> Self::Result {
+        let guid = match relay_guid(&msg.0.addr) {
+            Some(guid) if self.open.contains(&guid) => guid,
+            _ => return,
+        };
+        if let Some(client) = &self.client {
+            client.packet_with(
+                encode(RelayData::new(guid, msg.0.bytes)).freeze(),
+                unreliable(),
+            );
+        }
+    }
+}
+
+impl Handler<Open> for RelayClient {
+    type Result = ();
+    fn handle(&mut self, msg: Open, _ctx: &mut Self::Context) -> Self::Result {
+        self.request(msg.0, msg.1);
+    }
+}
+
+impl Handler<Close> for RelayClient {
+    type Result = ();
+    fn handle(&mut self, msg: Close, _ctx: &mut Self::Context) -> Self::Result {
+        self.queued.retain(|x| x.0 != msg.0);
+        if self.pending.remove(&msg.0) | self.open.remove(&msg.0) {
+            self.send(RelayClosed::new(msg.0));
+        }
+    }
+}
+
+impl Handler<Shutdown> for RelayClient {
+    type Result = ();
+    fn handle(&mut self, _msg: Shutdown, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(client) = self.client.take() {
+            client.disconnect();
+        }
+        ctx.stop();
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Open(u64, u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Close(u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Shutdown;
+
+#[derive(Clone)]
+pub struct RelayHandle {
+    addr: Addr<RelayClient>,
+}
+
+impl RelayHandle {
+    pub fn open(&self, guid: u64, token: u64) {
+        self.addr.do_send(Open(guid, token));
+    }
+    pub fn close(&self, guid: u64) {
+        self.addr.do_send(Close(guid));
+    }
+    pub fn shutdown(&self) {
+        self.addr.do_send(Shutdown);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::channel::oneshot;
+
+use crate::packets::{Reliability, ORDER_CHANNELS};
+
+static NEXT_RECEIPT: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Immediate,
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Clone, Debug)]
+pub struct SendOptions {
+    pub reliability: Reliability,
+    pub channel: u8,
+    pub priority: Priority,
+}
+
+impl SendOptions {
+    pub fn new() -> Self {
+        Self {
+            reliability: Reliability::ReliableOrdered,
+            channel: 0,
+            priority: Priority::Medium,
+        }
+    }
+    pub fn reliability(mut self, reliability: Reliability) -> Self {
+        self.reliability = reliability;
+        self
+    }
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = channel.min(ORDER_CHANNELS - 1);
+        self
+    }
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Reject,
+    DropUnreliable,
+    Disconnect,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueueLimits {
+    pub max_bytes: usize,
+    pub max_frames: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl QueueLimits {
+    pub fn new(max_bytes: usize, max_frames: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            max_bytes,
+            max_frames,
+            policy,
+        }
+    }
+    pub fn unbounded() -> Self {
+        Self::new(usize::MAX, usize::MAX, OverflowPolicy::Reject)
+    }
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self::unbounded()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub bytes_per_sec: u64,
+    pub burst: u64,
+}
+
+impl RateLimit {
+    pub fn new(bytes_per_sec: u64, burst: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            burst,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Coalescing {
+    pub enabled: bool,
+    pub max_delay: Duration,
+    pub max_bytes: usize,
+}
+
+impl Coalescing {
+    pub fn new(max_delay: Duration, max_bytes: usize) -> Self {
+        Self {
+            enabled: true,
+            max_delay,
+            max_bytes,
+        }
+    }
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            max_delay: Duration::ZERO,
+            max_bytes: 0,
+        }
+    }
+}
+
+impl Default for Coalescing {
+    fn default() -> Self {
+        Self::new(Duration::ZERO, usize::MAX)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueDepth {
+    pub frames: usize,
+    pub bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrySendError {
+    QueueFull,
+    Closed,
+}
+
+impl fmt::Display for TrySendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QueueFull => f.write_str("send queue is full"),
+            Self::Closed => f.write_str("connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for TrySendError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Acked,
+    Lost,
+}
+
+pub struct Receipt {
+    id: u32,
+    rx: oneshot::Receiver<Delivery>,
+}
+
+impl Receipt {
+    pub(crate) fn new() -> (Self, ReceiptSender) {
+        let id = NEXT_RECEIPT.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        (Self { id, rx }, ReceiptSender { id, tx })
+    }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Future for Receipt {
+    type Output = Delivery;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|x| x.unwrap_or(Delivery::Lost))
+    }
+}
+
+pub(crate) struct ReceiptSender {
+    pub id: u32,
+    pub tx: oneshot::Sender<Delivery>,
+}
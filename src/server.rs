@@ -1,15 +1,40 @@
 use actix::{dev::ToEnvelope, prelude::*};
-use bytes::BytesMut;
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use bytes::{Bytes, BytesMut};
+use futures::{channel::oneshot, future::join_all};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
 
+#[cfg(all(feature = "batch-io", target_os = "linux"))]
+use crate::batch::{BatchConfig, BatchUdpActor};
 use crate::{
-    macros::unwrap_or_return,
+    capture::SetCapture,
+    listener::Listener,
+    macros::{debug, info, span, unwrap_or_return, Span},
+    metrics::{HandshakeFailure, ServerMetrics},
+    pacing::{SharedBucket, TokenBucket},
     packets::*,
+    send::{
+        Coalescing, Priority, QueueDepth, QueueLimits, RateLimit, Receipt, ReceiptSender,
+        SendOptions, TrySendError,
+    },
     session::{time, ReceivedDatagram, Session, SessionEnd},
-    udp::{ReceivedUdp, SendUdp, UdpActor, UdpPacket},
+    transport::MemoryNetwork,
+    udp::{ReceivedUdp, SendUdp, Transport, UdpActor, UdpPacket},
     RAKNET_PROTOCOL_VERSION,
 };
 
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+const LOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+const LOAD_SMOOTHING: f64 = 0.5;
+
+pub(crate) type Routes = Arc<RwLock<HashMap<SocketAddr, Addr<ServerConn>>>>;
+
 #[derive(Clone)]
 pub struct ConnectionHandle {
     addr: Addr<ServerConn>,
@@ -20,35 +45,93 @@ impl ConnectionHandle {
     pub fn send(&self, bytes: BytesMut) {
         self.addr.do_send(SendPacket(bytes));
     }
+    pub async fn try_send(&self, bytes: BytesMut) -> Result<(), TrySendError> {
+        self.addr
+            .send(TrySendPacket(bytes))
+            .await
+            .unwrap_or(Err(TrySendError::Closed))
+    }
+    pub async fn queue_depth(&self) -> Option<QueueDepth> {
+        self.addr.send(GetInfo).await.ok().map(|x| x.queue)
+    }
+    pub fn send_with(&self, bytes: Bytes, options: SendOptions) {
+        self.addr.do_send(SendFrame {
+            payload: bytes,
+            options,
+            receipt: None,
+        });
+    }
+    pub fn send_with_receipt(&self, bytes: Bytes, options: SendOptions) -> Receipt {
+        let (receipt, sender) = Receipt::new();
+        self.addr.do_send(SendFrame {
+            payload: bytes,
+            options,
+            receipt: Some(sender),
+        });
+        receipt
+    }
+    pub fn flush(&self) {
+        self.addr.do_send(FlushConn);
+    }
+    pub fn set_coalescing(&self, coalescing: Coalescing) {
+        self.addr.do_send(SetCoalescing(coalescing));
+    }
+    pub fn set_bandwidth(&self, limit: Option<RateLimit>) {
+        self.addr.do_send(LimitConn(limit));
+    }
     pub fn disconnect(&self) {
         self.addr.do_send(DisconnectConn);
     }
+    pub fn disconnect_now(&self) {
+        self.addr.do_send(DisconnectNow);
+    }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub enum RakServerEvent {
     Connected(ConnectionHandle),
-    Packet(ConnectionHandle, BytesMut),
+    Packet(ConnectionHandle, Bytes),
     Disconnected(SocketAddr, u64),
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub enum RakServerNotice {
+    Migrated(ConnectionHandle, SocketAddr),
+    Replaced(SocketAddr, u64),
+}
+
 pub struct RakServer<T>
 where
     T: Actor,
     T: Handler<RakServerEvent>,
     <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
 {
-    udp: Addr<UdpActor<Self>>,
+    udp: Vec<Transport>,
     handler: Addr<T>,
     conns: HashMap<SocketAddr, Addr<ServerConn>>,
-    connected_id: Vec<u64>,
+    routes: Routes,
+    connected_id: HashMap<u64, SocketAddr>,
     motd: String,
     guid: u64,
+    linger: Option<Duration>,
+    notices: Option<Recipient<RakServerNotice>>,
+    migration: bool,
+    takeover: TakeoverPolicy,
+    pending: HashSet<u64>,
+    handshaking: HashSet<SocketAddr>,
+    limits: QueueLimits,
+    coalescing: Coalescing,
+    bandwidth: Option<RateLimit>,
+    global_bandwidth: Option<SharedBucket>,
 
-    udp_worker: Arbiter,
+    udp_workers: Vec<Arbiter>,
 
     session_worker: SessionWorker,
+    metrics: Arc<ServerMetrics>,
+    shutting_down: bool,
+    shutdown_waiters: Vec<oneshot::Sender<()>>,
 }
 
 impl<T> RakServer<T>
@@ -64,16 +147,153 @@ where
         handler: Addr<T>,
         thread: u32,
     ) -> Addr<Self> {
-        let udp_worker = Arbiter::new();
+        Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            1,
+            move |addr, arbiters, _routes, metrics| {
+                vec![Transport::new(UdpActor::new(
+                    socket,
+                    addr,
+                    &arbiters[0],
+                    Some(metrics),
+                ))]
+            },
+        )
+    }
+
+    #[cfg(all(feature = "batch-io", target_os = "linux"))]
+    pub fn new_batched(
+        socket: std::net::UdpSocket,
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+        config: BatchConfig,
+    ) -> std::io::Result<Addr<Self>> {
+        socket.set_nonblocking(true)?;
+        Ok(Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            1,
+            move |addr, arbiters, _routes, metrics| {
+                vec![Transport::new(BatchUdpActor::new(
+                    socket,
+                    config,
+                    addr,
+                    &arbiters[0],
+                    Some(metrics),
+                ))]
+            },
+        ))
+    }
+
+    pub fn new_in_memory(
+        network: &MemoryNetwork,
+        local: SocketAddr,
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+    ) -> Addr<Self> {
+        let network = network.clone();
+        Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            1,
+            move |addr, arbiters, _routes, metrics| {
+                vec![Transport::new(network.bind(
+                    local,
+                    addr.recipient(),
+                    &arbiters[0],
+                    Some(metrics),
+                ))]
+            },
+        )
+    }
+
+    pub fn new_sharded(
+        listener: Listener,
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+    ) -> Addr<Self> {
+        let sockets = listener.into_sockets();
+        Self::start(
+            guid,
+            motd,
+            handler,
+            thread,
+            sockets.len(),
+            move |addr, arbiters, routes, metrics| {
+                sockets
+                    .into_iter()
+                    .zip(arbiters)
+                    .enumerate()
+                    .map(|(index, (socket, arbiter))| {
+                        let routes = routes.clone();
+                        let server = addr.clone().recipient();
+                        let shard = Shard::start_in_arbiter(arbiter, move |_ctx| Shard {
+                            index,
+                            routes,
+                            server,
+                        });
+                        Transport::new(UdpActor::from_std(
+                            socket,
+                            shard,
+                            arbiter,
+                            Some(metrics.clone()),
+                        ))
+                    })
+                    .collect()
+            },
+        )
+    }
+
+    pub(crate) fn start<F>(
+        guid: u64,
+        motd: String,
+        handler: Addr<T>,
+        thread: u32,
+        shards: usize,
+        transport: F,
+    ) -> Addr<Self>
+    where
+        F: FnOnce(Addr<Self>, &[Arbiter], &Routes, Arc<ServerMetrics>) -> Vec<Transport>,
+    {
+        let udp_workers = (0..shards).map(|_| Arbiter::new()).collect::<Vec<_>>();
+        let metrics = Arc::new(ServerMetrics::new(thread));
+        let routes = Routes::default();
         Self::create(|ctx| Self {
-            udp: UdpActor::new(socket, ctx.address(), &udp_worker),
+            udp: transport(ctx.address(), &udp_workers, &routes, metrics.clone()),
             handler,
             conns: HashMap::new(),
-            connected_id: vec![],
+            routes,
+            connected_id: HashMap::new(),
             motd,
             guid,
-            udp_worker,
-            session_worker: SessionWorker::new(thread),
+            linger: None,
+            notices: None,
+            migration: false,
+            takeover: TakeoverPolicy::Reject,
+            pending: HashSet::new(),
+            handshaking: HashSet::new(),
+            limits: QueueLimits::default(),
+            coalescing: Coalescing::default(),
+            bandwidth: None,
+            global_bandwidth: None,
+            udp_workers,
+            session_worker: SessionWorker::new(thread, metrics.clone()),
+            metrics,
+            shutting_down: false,
+            shutdown_waiters: vec![],
         })
     }
 }
@@ -85,8 +305,16 @@ where
     <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
 {
     type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(LOAD_INTERVAL, |me, _ctx| me.session_worker.sample());
+    }
     fn stopped(&mut self, _ctx: &mut Self::Context) {
-        self.udp_worker.stop();
+        for waiter in self.shutdown_waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+        for udp_worker in self.udp_workers.iter() {
+            udp_worker.stop();
+        }
         self.session_worker.stop();
     }
 }
@@ -99,76 +327,217 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: ReceivedUdp, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(conn) = self.conns.get(&msg.0.addr) {
+        self.received(0, msg, ctx);
+    }
+}
+
+impl<T> Handler<ShardReceived> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: ShardReceived, ctx: &mut Self::Context) -> Self::Result {
+        self.received(msg.0, ReceivedUdp(msg.1), ctx);
+    }
+}
+
+impl<T> RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    fn received(&mut self, shard: usize, msg: ReceivedUdp, ctx: &mut Context<Self>) {
+        if let (Some(conn), false) = (self.conns.get(&msg.0.addr), is_handshake(&msg.0.bytes)) {
             conn.do_send(msg);
             return;
         }
 
+        let udp = &self.udp[shard];
         let buff: &[u8] = &msg.0.bytes;
+        if self.shutting_down && buff[0] != UnconnectedPing::ID {
+            return;
+        }
         match buff[0] {
             UnconnectedPing::ID => {
                 let ping = unwrap_or_return!(decode::<UnconnectedPing>(buff));
+                debug!(addr = %msg.0.addr, "unconnected ping");
                 let pong = UnconnectedPong::new(ping.time, self.guid, self.motd.clone());
-                self.udp.do_send(SendUdp(UdpPacket {
-                    bytes: encode(pong),
-                    addr: msg.0.addr,
-                }));
+                udp.send_to(encode(pong), msg.0.addr);
             }
             OpenConnectionRequest1::ID => {
                 let request1 = unwrap_or_return!(decode::<OpenConnectionRequest1>(buff));
+                debug!(
+                    addr = %msg.0.addr,
+                    protocol = request1.protocol_version,
+                    mtu = request1.mtu_size,
+                    "open connection request 1"
+                );
                 if request1.protocol_version != RAKNET_PROTOCOL_VERSION {
+                    info!(
+                        addr = %msg.0.addr,
+                        protocol = request1.protocol_version,
+                        "handshake refused: incompatible protocol"
+                    );
+                    self.metrics
+                        .handshake_failed(HandshakeFailure::IncompatibleProtocol);
                     let protocol_version =
                         IncompatibleProtocolVersion::new(RAKNET_PROTOCOL_VERSION, self.guid);
-                    self.udp.do_send(SendUdp(UdpPacket {
-                        bytes: encode(protocol_version),
-                        addr: msg.0.addr,
-                    }));
+                    udp.send_to(encode(protocol_version), msg.0.addr);
                     return;
                 }
                 let reply = OpenConnectionReply1::new(self.guid, false, request1.mtu_size);
-                self.udp.do_send(SendUdp(UdpPacket {
-                    bytes: encode(reply),
-                    addr: msg.0.addr,
-                }));
+                udp.send_to(encode(reply), msg.0.addr);
             }
             OpenConnectionRequest2::ID => {
                 let request2 = unwrap_or_return!(decode::<OpenConnectionRequest2>(buff));
+                debug!(
+                    addr = %msg.0.addr,
+                    guid = request2.guid,
+                    mtu = request2.mtu,
+                    "open connection request 2"
+                );
 
-                if self.connected_id.contains(&request2.guid) {
-                    let already_connected = AlreadyConnected::new(request2.guid);
-                    let data = encode(already_connected);
-                    self.udp.do_send(SendUdp(UdpPacket {
-                        bytes: data,
-                        addr: msg.0.addr,
-                    }));
+                if self.conns.contains_key(&msg.0.addr) {
+                    if self.connected_id.get(&request2.guid) != Some(&msg.0.addr) {
+                        return;
+                    }
+                    // a retransmit while the handshake runs, the session is kept
+                    if self.handshaking.contains(&msg.0.addr) {
+                        let reply2 =
+                            OpenConnectionReply2::new(self.guid, msg.0.addr, request2.mtu, false);
+                        udp.send_to(encode(reply2), msg.0.addr);
+                        return;
+                    }
+                }
+                if self.pending.contains(&request2.guid) {
                     return;
                 }
-
-                let reply2 = OpenConnectionReply2::new(self.guid, msg.0.addr, request2.mtu, false);
-                self.udp.do_send(SendUdp(UdpPacket {
-                    bytes: encode(reply2),
-                    addr: msg.0.addr,
-                }));
-
-                let arbiter = self.session_worker.add(msg.0.addr);
-                self.conns.insert(
-                    msg.0.addr,
-                    ServerConn::new(
-                        self.udp.clone().recipient::<SendUdp>(),
-                        request2.mtu,
-                        request2.guid,
-                        msg.0.addr,
-                        self.handler.clone().recipient::<RakServerEvent>(),
-                        ctx.address().recipient::<ConnectionEnd>(),
-                        arbiter,
-                    ),
-                );
-
-                self.connected_id.push(request2.guid);
+                let from = match self.connected_id.get(&request2.guid).copied() {
+                    Some(from) => from,
+                    None => {
+                        self.accept(shard, msg.0.addr, request2, ctx);
+                        return;
+                    }
+                };
+                if let (Some(cookie), true) =
+                    (request2.cookie, self.migration && from != msg.0.addr)
+                {
+                    self.migrate(shard, from, msg.0.addr, request2, cookie, ctx);
+                    return;
+                }
+                match self.takeover {
+                    TakeoverPolicy::Reject => self.refuse(shard, msg.0.addr, request2.guid),
+                    TakeoverPolicy::Replace => {
+                        self.replace(from, request2.guid);
+                        self.accept(shard, msg.0.addr, request2, ctx);
+                    }
+                    TakeoverPolicy::RejectIfActive(window) => {
+                        let conn = match self.conns.get(&from) {
+                            Some(conn) => conn.clone(),
+                            None => return,
+                        };
+                        let to = msg.0.addr;
+                        self.pending.insert(request2.guid);
+                        let info = conn.send(GetInfo);
+                        ctx.spawn(info.into_actor(self).map(move |info, me, ctx| {
+                            me.pending.remove(&request2.guid);
+                            if me.connected_id.get(&request2.guid) != Some(&from) {
+                                return;
+                            }
+                            match info {
+                                Ok(info) if info.idle < window => {
+                                    me.refuse(shard, to, request2.guid)
+                                }
+                                _ => {
+                                    me.replace(from, request2.guid);
+                                    me.accept(shard, to, request2, ctx);
+                                }
+                            }
+                        }));
+                    }
+                }
             }
             _ => {}
         }
     }
+
+    fn accept(
+        &mut self,
+        shard: usize,
+        address: SocketAddr,
+        request2: OpenConnectionRequest2,
+        ctx: &mut Context<Self>,
+    ) {
+        let udp = &self.udp[shard];
+        let reply2 = OpenConnectionReply2::new(self.guid, address, request2.mtu, false);
+        udp.send_to(encode(reply2), address);
+
+        self.metrics.handshake_started();
+        let (worker, arbiter) = self.session_worker.add(address, request2.guid);
+        let conn = ServerConn::new(
+            udp.send.clone(),
+            request2.mtu,
+            request2.guid,
+            address,
+            worker,
+            self.handler.clone().recipient::<RakServerEvent>(),
+            ctx.address().recipient::<ConnectionEnd>(),
+            ctx.address().recipient::<HandshakeAccepted>(),
+            arbiter,
+            self.metrics.clone(),
+        );
+        if self.linger.is_some() {
+            conn.do_send(SetLinger(self.linger));
+        }
+        if self.notices.is_some() {
+            conn.do_send(SetNotices(self.notices.clone()));
+        }
+        conn.do_send(SetQueueLimits(self.limits));
+        conn.do_send(SetCoalescing(self.coalescing));
+        if self.bandwidth.is_some() || self.global_bandwidth.is_some() {
+            conn.do_send(LimitConn(self.bandwidth));
+            conn.do_send(ShareBucket(self.global_bandwidth.clone()));
+        }
+        self.routes.write().unwrap().insert(address, conn.clone());
+        self.conns.insert(address, conn);
+        self.handshaking.insert(address);
+
+        self.connected_id.insert(request2.guid, address);
+    }
+
+    fn refuse(&mut self, shard: usize, address: SocketAddr, guid: u64) {
+        info!(addr = %address, guid, "handshake refused: already connected");
+        self.metrics
+            .handshake_failed(HandshakeFailure::AlreadyConnected);
+        let already_connected = AlreadyConnected::new(guid);
+        self.udp[shard].send_to(encode(already_connected), address);
+    }
+
+    fn replace(&mut self, from: SocketAddr, guid: u64) {
+        info!(addr = %from, guid, "replacing stale session");
+        if let Some(conn) = self.conns.remove(&from) {
+            conn.do_send(ReplaceConn);
+        }
+        self.routes.write().unwrap().remove(&from);
+        self.session_worker.delete(from);
+        self.handshaking.remove(&from);
+        self.connected_id.remove(&guid);
+    }
+}
+
+impl<T> Handler<HandshakeAccepted> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: HandshakeAccepted, _ctx: &mut Self::Context) -> Self::Result {
+        self.handshaking.remove(&msg.0);
+    }
 }
 
 impl<T> Handler<ConnectionEnd> for RakServer<T>
@@ -178,12 +547,16 @@ where
     <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
 {
     type Result = ();
-    fn handle(&mut self, msg: ConnectionEnd, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: ConnectionEnd, ctx: &mut Self::Context) -> Self::Result {
         self.conns.remove(&msg.0);
+        self.routes.write().unwrap().remove(&msg.0);
         self.session_worker.delete(msg.0);
-        if self.connected_id.contains(&msg.1) {
-            let index = self.connected_id.iter().position(|x| *x == msg.1).unwrap();
-            self.connected_id.remove(index);
+        self.handshaking.remove(&msg.0);
+        if self.connected_id.get(&msg.1) == Some(&msg.0) {
+            self.connected_id.remove(&msg.1);
+        }
+        if self.shutting_down && self.conns.is_empty() {
+            ctx.stop();
         }
     }
 }
@@ -200,78 +573,577 @@ where
     }
 }
 
+impl<T> RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    fn migrate(
+        &mut self,
+        shard: usize,
+        from: SocketAddr,
+        to: SocketAddr,
+        request2: OpenConnectionRequest2,
+        cookie: u64,
+        ctx: &mut Context<Self>,
+    ) {
+        let conn = match self.conns.get(&from) {
+            Some(conn) => conn.clone(),
+            None => return,
+        };
+        let guid = request2.guid;
+        if self.conns.contains_key(&to) || !self.pending.insert(guid) {
+            return;
+        }
+        let proof = conn.send(ProveOwnership(cookie));
+        ctx.spawn(proof.into_actor(self).map(move |proven, me, _ctx| {
+            me.pending.remove(&guid);
+            if !proven.unwrap_or(false)
+                || me.conns.contains_key(&to)
+                || me.connected_id.get(&guid) != Some(&from)
+            {
+                info!(addr = %to, guid, "migration refused");
+                me.refuse(shard, to, guid);
+                return;
+            }
+            let udp = &me.udp[shard];
+            info!(from = %from, to = %to, guid, "migrating session");
+            let conn = me.conns.remove(&from).unwrap();
+            {
+                let mut routes = me.routes.write().unwrap();
+                routes.remove(&from);
+                routes.insert(to, conn.clone());
+            }
+            me.conns.insert(to, conn.clone());
+            me.connected_id.insert(guid, to);
+            me.session_worker.rekey(from, to);
+            let reply2 = OpenConnectionReply2::new(me.guid, to, request2.mtu, false);
+            udp.send_to(encode(reply2), to);
+            conn.do_send(MigrateConn {
+                address: to,
+                udp: udp.send.clone(),
+            });
+        }));
+    }
+}
+
+impl<T> Handler<SetTakeoverPolicy> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetTakeoverPolicy, _ctx: &mut Self::Context) -> Self::Result {
+        self.takeover = msg.0;
+    }
+}
+
+impl<T> Handler<SetMigration> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetMigration, _ctx: &mut Self::Context) -> Self::Result {
+        self.migration = msg.0;
+    }
+}
+
+impl<T> Handler<SetNotices> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetNotices, _ctx: &mut Self::Context) -> Self::Result {
+        for conn in self.conns.values() {
+            conn.do_send(SetNotices(msg.0.clone()));
+        }
+        self.notices = msg.0;
+    }
+}
+
+impl<T> Handler<SetLinger> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetLinger, _ctx: &mut Self::Context) -> Self::Result {
+        self.linger = msg.0;
+        for conn in self.conns.values() {
+            conn.do_send(msg);
+        }
+    }
+}
+
+impl<T> Handler<SetQueueLimits> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetQueueLimits, _ctx: &mut Self::Context) -> Self::Result {
+        self.limits = msg.0;
+        for conn in self.conns.values() {
+            conn.do_send(msg);
+        }
+    }
+}
+
+impl<T> Handler<SetCoalescing> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetCoalescing, _ctx: &mut Self::Context) -> Self::Result {
+        self.coalescing = msg.0;
+        for conn in self.conns.values() {
+            conn.do_send(msg);
+        }
+    }
+}
+
+impl<T> Handler<SetBandwidth> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetBandwidth, _ctx: &mut Self::Context) -> Self::Result {
+        self.bandwidth = msg.per_connection;
+        self.global_bandwidth = msg.global.map(TokenBucket::shared);
+        for conn in self.conns.values() {
+            conn.do_send(LimitConn(self.bandwidth));
+            conn.do_send(ShareBucket(self.global_bandwidth.clone()));
+        }
+    }
+}
+
+impl<T> Handler<SetCapture> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetCapture, _ctx: &mut Self::Context) -> Self::Result {
+        for udp in self.udp.iter() {
+            unwrap_or_return!(udp.capture.do_send(SetCapture(msg.0.clone())));
+        }
+    }
+}
+
+impl<T> Handler<GetMetrics> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = MessageResult<GetMetrics>;
+    fn handle(&mut self, _msg: GetMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.metrics.clone())
+    }
+}
+
+impl<T> Handler<SetAffinity> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetAffinity, _ctx: &mut Self::Context) -> Self::Result {
+        self.session_worker.set_affinity(msg.0);
+    }
+}
+
+impl<T> Handler<ListWorkers> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = MessageResult<ListWorkers>;
+    fn handle(&mut self, _msg: ListWorkers, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.session_worker.info())
+    }
+}
+
+impl<T> RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    fn resolve(&self, peer: &Peer) -> Option<SocketAddr> {
+        match peer {
+            Peer::Guid(guid) => self.connected_id.get(guid).copied(),
+            Peer::Address(address) => Some(*address),
+        }
+    }
+}
+
+impl<T> Handler<Broadcast> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: Broadcast, _ctx: &mut Self::Context) -> Self::Result {
+        let send = |conn: &Addr<ServerConn>| {
+            conn.do_send(SendFrame {
+                payload: msg.payload.clone(),
+                options: msg.options.clone(),
+                receipt: None,
+            })
+        };
+        match &msg.target {
+            BroadcastTarget::All => self.conns.values().for_each(send),
+            BroadcastTarget::Only(peers) => {
+                let targets = peers
+                    .iter()
+                    .filter_map(|x| self.resolve(x))
+                    .collect::<HashSet<_>>();
+                targets
+                    .iter()
+                    .filter_map(|x| self.conns.get(x))
+                    .for_each(send);
+            }
+            BroadcastTarget::Except(peers) => {
+                let excluded = peers
+                    .iter()
+                    .filter_map(|x| self.resolve(x))
+                    .collect::<HashSet<_>>();
+                self.conns
+                    .iter()
+                    .filter(|x| !excluded.contains(x.0))
+                    .for_each(|x| send(x.1));
+            }
+        }
+    }
+}
+
+impl<T> Handler<ListConnections> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ResponseFuture<Vec<ConnectionInfo>>;
+    fn handle(&mut self, _msg: ListConnections, _ctx: &mut Self::Context) -> Self::Result {
+        let requests = self
+            .conns
+            .values()
+            .map(|x| x.send(GetInfo))
+            .collect::<Vec<_>>();
+        Box::pin(async move {
+            join_all(requests)
+                .await
+                .into_iter()
+                .filter_map(|x| x.ok())
+                .collect()
+        })
+    }
+}
+
+impl<T> Handler<FindConnection> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ResponseFuture<Option<ConnectionHandle>>;
+    fn handle(&mut self, msg: FindConnection, _ctx: &mut Self::Context) -> Self::Result {
+        let conn = self
+            .resolve(&msg.0)
+            .and_then(|x| self.conns.get(&x))
+            .cloned();
+        Box::pin(async move {
+            let conn = conn?;
+            let info = conn.send(GetInfo).await.ok()?;
+            if info.state != ConnectionState::Connected {
+                return None;
+            }
+            Some(ConnectionHandle {
+                addr: conn,
+                address: info.address,
+                guid: info.guid,
+            })
+        })
+    }
+}
+
+impl<T> Handler<ConnectionCount> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = usize;
+    fn handle(&mut self, _msg: ConnectionCount, _ctx: &mut Self::Context) -> Self::Result {
+        self.metrics.connected_sessions() as usize
+    }
+}
+
+impl<T> Handler<Shutdown> for RakServer<T>
+where
+    T: Actor,
+    T: Handler<RakServerEvent>,
+    <T as actix::Actor>::Context: ToEnvelope<T, RakServerEvent>,
+{
+    type Result = ResponseFuture<()>;
+    fn handle(&mut self, msg: Shutdown, ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.shutdown_waiters.push(tx);
+        if !self.shutting_down {
+            self.shutting_down = true;
+            info!(sessions = self.conns.len(), "shutting down");
+            for conn in self.conns.values() {
+                conn.do_send(LingerConn(msg.timeout));
+            }
+            if self.conns.is_empty() {
+                ctx.stop();
+            } else {
+                ctx.run_later(msg.timeout + SHUTDOWN_GRACE, |_me, ctx| ctx.stop());
+            }
+        }
+        Box::pin(async move {
+            let _ = rx.await;
+        })
+    }
+}
+
+struct Shard {
+    index: usize,
+    routes: Routes,
+    server: Recipient<ShardReceived>,
+}
+
+impl Actor for Shard {
+    type Context = Context<Self>;
+}
+
+impl Handler<ReceivedUdp> for Shard {
+    type Result = ();
+    fn handle(&mut self, msg: ReceivedUdp, _ctx: &mut Self::Context) -> Self::Result {
+        if let (Some(conn), false) = (
+            self.routes.read().unwrap().get(&msg.0.addr),
+            is_handshake(&msg.0.bytes),
+        ) {
+            conn.do_send(msg);
+            return;
+        }
+        unwrap_or_return!(self.server.do_send(ShardReceived(self.index, msg.0)));
+    }
+}
+
+fn is_handshake(buff: &[u8]) -> bool {
+    matches!(
+        buff.first(),
+        Some(&OpenConnectionRequest1::ID) | Some(&OpenConnectionRequest2::ID)
+    )
+}
+
+struct Worker {
+    arbiter: Arbiter,
+    sessions: u32,
+    load: f64,
+    packets: u64,
+}
+
 pub(crate) struct SessionWorker {
-    session: HashMap<SocketAddr, u32>,
-    workers: HashMap<u32, (Arbiter, u32)>,
+    session: HashMap<SocketAddr, (u32, Option<u64>)>,
+    workers: Vec<Worker>,
+    groups: HashMap<u64, (u32, u32)>,
+    affinity: Option<Affinity>,
+    sampled: Instant,
+    metrics: Arc<ServerMetrics>,
 }
 
 impl SessionWorker {
-    pub fn new(threads: u32) -> Self {
-        let mut workers = HashMap::new();
-        for i in 0..threads {
-            let thread = Arbiter::new();
-            workers.insert(i, (thread, 0));
-        }
+    pub fn new(threads: u32, metrics: Arc<ServerMetrics>) -> Self {
+        let workers = (0..threads)
+            .map(|_| Worker {
+                arbiter: Arbiter::new(),
+                sessions: 0,
+                load: 0.0,
+                packets: 0,
+            })
+            .collect();
         Self {
             session: HashMap::new(),
             workers,
+            groups: HashMap::new(),
+            affinity: None,
+            sampled: Instant::now(),
+            metrics,
+        }
+    }
+
+    pub fn set_affinity(&mut self, affinity: Option<Affinity>) {
+        self.affinity = affinity;
+    }
+
+    pub fn add(&mut self, address: SocketAddr, guid: u64) -> (u32, &Arbiter) {
+        let group = self.affinity.as_ref().and_then(|x| (x.0)(address, guid));
+        let pinned = group.and_then(|x| self.groups.get(&x)).map(|x| x.0);
+        let y = pinned.unwrap_or_else(|| self.least_loaded());
+        if let Some(group) = group {
+            self.groups.entry(group).or_insert((y, 0)).1 += 1;
         }
+        let per_session = self.per_session_load();
+        let worker = &mut self.workers[y as usize];
+        worker.sessions += 1;
+        worker.load += per_session;
+        self.metrics.worker_added(y);
+        self.session.insert(address, (y, group));
+        (y, &self.workers[y as usize].arbiter)
     }
 
-    pub fn add(&mut self, address: SocketAddr) -> &Arbiter {
-        let y = *self.workers.iter().min_by_key(|x| x.1 .1).unwrap().0;
-        self.workers.get_mut(&y).unwrap().1 += 1;
-        self.session.insert(address, y);
-        &self.workers.get(&y).unwrap().0
+    pub fn rekey(&mut self, from: SocketAddr, to: SocketAddr) {
+        if let Some(session) = self.session.remove(&from) {
+            self.session.insert(to, session);
+        }
     }
 
     pub fn delete(&mut self, address: SocketAddr) {
-        if let Some(i) = self.session.remove(&address) {
-            self.workers.get_mut(&i).unwrap().1 -= 1;
+        if let Some((i, group)) = self.session.remove(&address) {
+            self.workers[i as usize].sessions -= 1;
+            self.metrics.worker_removed(i);
+            if let Some(group) = group {
+                let count = &mut self.groups.get_mut(&group).unwrap().1;
+                *count -= 1;
+                if *count == 0 {
+                    self.groups.remove(&group);
+                }
+            }
+        }
+    }
+
+    pub fn sample(&mut self) {
+        let elapsed = self.sampled.elapsed().as_secs_f64();
+        self.sampled = Instant::now();
+        if elapsed <= 0.0 {
+            return;
+        }
+        let packets = self.metrics.worker_packets();
+        for (worker, packets) in self.workers.iter_mut().zip(packets) {
+            let rate = (packets - worker.packets) as f64 / elapsed;
+            worker.packets = packets;
+            worker.load = worker.load * LOAD_SMOOTHING + rate * (1.0 - LOAD_SMOOTHING);
+        }
+    }
+
+    pub fn info(&self) -> Vec<WorkerInfo> {
+        let mut info = self
+            .workers
+            .iter()
+            .enumerate()
+            .map(|(index, worker)| WorkerInfo {
+                index: index as u32,
+                sessions: worker.sessions,
+                load: worker.load,
+                peers: vec![],
+            })
+            .collect::<Vec<_>>();
+        for (address, (worker, _)) in self.session.iter() {
+            info[*worker as usize].peers.push(*address);
         }
+        info
     }
 
     pub fn stop(&mut self) {
-        for thread in self.workers.iter() {
-            let arbiter: &Arbiter = &thread.1 .0;
-            arbiter.stop()
+        for worker in self.workers.iter() {
+            worker.arbiter.stop()
+        }
+    }
+
+    fn least_loaded(&self) -> u32 {
+        self.workers
+            .iter()
+            .enumerate()
+            .min_by(|a, b| {
+                a.1.load
+                    .total_cmp(&b.1.load)
+                    .then(a.1.sessions.cmp(&b.1.sessions))
+            })
+            .unwrap()
+            .0 as u32
+    }
+
+    fn per_session_load(&self) -> f64 {
+        let sessions = self.session.len();
+        if sessions == 0 {
+            return 0.0;
         }
+        self.workers.iter().map(|x| x.load).sum::<f64>() / sessions as f64
     }
 }
 
 pub(crate) struct ServerConn {
     session: Session<Self>,
     handler: Recipient<RakServerEvent>,
+    notices: Option<Recipient<RakServerNotice>>,
     server: Recipient<ConnectionEnd>,
+    accepted: Recipient<HandshakeAccepted>,
     guid: u64,
     addr: SocketAddr,
+    worker: u32,
+    cookie: Option<u64>,
+    replaced: bool,
+    connected_since: Option<SystemTime>,
     disconnect_handle: Option<SpawnHandle>,
+    metrics: Arc<ServerMetrics>,
+    span: Span,
 }
 
 impl ServerConn {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         udp: Recipient<SendUdp>,
         mtu: u16,
         guid: u64,
         addr: SocketAddr,
+        worker: u32,
         handler: Recipient<RakServerEvent>,
         server: Recipient<ConnectionEnd>,
+        accepted: Recipient<HandshakeAccepted>,
         arbiter: &Arbiter,
+        metrics: Arc<ServerMetrics>,
     ) -> Addr<Self> {
         ServerConn::start_in_arbiter(arbiter, move |ctx| Self {
-            session: Session::<Self>::new(addr, mtu, udp, ctx.address()),
+            session: Session::<Self>::new(addr, mtu, udp, ctx.address(), Some(metrics.clone())),
             handler,
+            notices: None,
             server,
+            accepted,
             guid,
             addr,
+            worker,
+            cookie: None,
+            replaced: false,
+            connected_since: None,
             disconnect_handle: Some(ctx.run_later(Duration::from_secs(5), |me, _ctx| {
-                me.disconnect();
+                let _enter = me.span.clone().entered();
+                info!("handshake timed out");
+                me.metrics.handshake_failed(HandshakeFailure::Timeout);
+                me.session.disconnect_now();
             })),
+            metrics,
+            span: span!("session", %addr, guid),
         })
     }
-    fn disconnect(&mut self) {
-        self.session.disconnect()
-    }
     fn event(&mut self, event: RakServerEvent, ctx: &mut Context<Self>) {
         self.handler.do_send(event).unwrap_or_else(|e| {
             if let SendError::Closed(_event) = e {
@@ -285,6 +1157,7 @@ impl Actor for ServerConn {
     type Context = Context<Self>;
     fn started(&mut self, ctx: &mut Self::Context) {
         ctx.run_interval(Duration::from_millis(10), |me, _ctx| {
+            let _enter = me.span.clone().entered();
             me.session.update();
         });
     }
@@ -293,6 +1166,8 @@ impl Actor for ServerConn {
 impl Handler<ReceivedUdp> for ServerConn {
     type Result = ();
     fn handle(&mut self, msg: ReceivedUdp, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        self.metrics.worker_packet(self.worker);
         self.session.handle(msg);
     }
 }
@@ -300,10 +1175,13 @@ impl Handler<ReceivedUdp> for ServerConn {
 impl Handler<ReceivedDatagram> for ServerConn {
     type Result = ();
     fn handle(&mut self, msg: ReceivedDatagram, ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
         if let Some(handle) = self.disconnect_handle {
             match msg.0.data[0] {
                 ConnectionRequest::ID => {
                     let request = unwrap_or_return!(decode::<ConnectionRequest>(&msg.0.data));
+                    debug!("connection request");
+                    self.cookie = request.cookie;
                     let accept = ConnectionRequestAccepted::new(
                         self.addr,
                         request.time,
@@ -311,6 +1189,7 @@ impl Handler<ReceivedDatagram> for ServerConn {
                     );
                     self.session
                         .send_system_packet(accept, Reliability::ReliableOrdered);
+                    let _ = self.accepted.do_send(HandshakeAccepted(self.addr));
                 }
                 NewIncomingConnection::ID => {
                     let _connected =
@@ -320,6 +1199,9 @@ impl Handler<ReceivedDatagram> for ServerConn {
                         address: self.addr,
                         guid: self.guid,
                     };
+                    info!("connected");
+                    self.connected_since = Some(SystemTime::now());
+                    self.metrics.session_connected();
                     self.event(RakServerEvent::Connected(my_handle), ctx);
                     ctx.cancel_future(handle);
                     self.disconnect_handle = None;
@@ -337,10 +1219,57 @@ impl Handler<ReceivedDatagram> for ServerConn {
     }
 }
 
+impl Handler<ReplaceConn> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, _msg: ReplaceConn, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        info!("replaced by a new connection");
+        self.replaced = true;
+        self.session.disconnect_now();
+    }
+}
+
+impl Handler<ProveOwnership> for ServerConn {
+    type Result = bool;
+    fn handle(&mut self, msg: ProveOwnership, _ctx: &mut Self::Context) -> Self::Result {
+        self.disconnect_handle.is_none() && self.cookie == Some(msg.0)
+    }
+}
+
+impl Handler<MigrateConn> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: MigrateConn, ctx: &mut Self::Context) -> Self::Result {
+        let from = self.addr;
+        self.addr = msg.address;
+        self.session.rebind(msg.address, msg.udp);
+        self.span = span!("session", addr = %msg.address, guid = self.guid);
+        let _enter = self.span.clone().entered();
+        info!(from = %from, "migrated");
+        let my_handle = ConnectionHandle {
+            addr: ctx.address(),
+            address: self.addr,
+            guid: self.guid,
+        };
+        if let Some(notices) = &self.notices {
+            let _ = notices.do_send(RakServerNotice::Migrated(my_handle, from));
+        }
+    }
+}
+
 impl Handler<SessionEnd> for ServerConn {
     type Result = ();
     fn handle(&mut self, _msg: SessionEnd, ctx: &mut Self::Context) -> Self::Result {
-        unwrap_or_return!(self.server.do_send(ConnectionEnd(self.addr, self.guid)));
+        let _enter = self.span.clone().entered();
+        if self.disconnect_handle.is_none() {
+            self.metrics.session_disconnected();
+        }
+        if self.replaced {
+            if let Some(notices) = &self.notices {
+                let _ = notices.do_send(RakServerNotice::Replaced(self.addr, self.guid));
+            }
+        } else {
+            unwrap_or_return!(self.server.do_send(ConnectionEnd(self.addr, self.guid)));
+        }
         self.event(RakServerEvent::Disconnected(self.addr, self.guid), ctx);
         ctx.terminate();
     }
@@ -349,14 +1278,132 @@ impl Handler<SessionEnd> for ServerConn {
 impl Handler<SendPacket> for ServerConn {
     type Result = ();
     fn handle(&mut self, msg: SendPacket, _ctx: &mut Self::Context) -> Self::Result {
-        self.session.send_to(msg.0);
+        let _enter = self.span.clone().entered();
+        self.session.send_to(msg.0.freeze());
+    }
+}
+
+impl Handler<TrySendPacket> for ServerConn {
+    type Result = Result<(), TrySendError>;
+    fn handle(&mut self, msg: TrySendPacket, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        self.session
+            .try_send_with(msg.0.freeze(), &SendOptions::new())
+    }
+}
+
+impl Handler<SetQueueLimits> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: SetQueueLimits, _ctx: &mut Self::Context) -> Self::Result {
+        self.session.set_queue_limits(msg.0);
+    }
+}
+
+impl Handler<SetCoalescing> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: SetCoalescing, _ctx: &mut Self::Context) -> Self::Result {
+        self.session.set_coalescing(msg.0);
+    }
+}
+
+impl Handler<LimitConn> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: LimitConn, _ctx: &mut Self::Context) -> Self::Result {
+        self.session.set_bandwidth(msg.0);
+    }
+}
+
+impl Handler<ShareBucket> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: ShareBucket, _ctx: &mut Self::Context) -> Self::Result {
+        self.session.set_global_bandwidth(msg.0);
+    }
+}
+
+impl Handler<FlushConn> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, _msg: FlushConn, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        self.session.flush();
+    }
+}
+
+impl Handler<SendFrame> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: SendFrame, _ctx: &mut Self::Context) -> Self::Result {
+        if self.disconnect_handle.is_some() {
+            return;
+        }
+        let _enter = self.span.clone().entered();
+        match msg.receipt {
+            Some(receipt) => self
+                .session
+                .send_with_receipt(msg.payload, &msg.options, receipt),
+            None => self.session.send_with(msg.payload, &msg.options),
+        }
+    }
+}
+
+impl Handler<LingerConn> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: LingerConn, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        if self.disconnect_handle.is_some() {
+            self.session.disconnect_now();
+        } else {
+            self.session.linger(msg.0);
+        }
+    }
+}
+
+impl Handler<GetInfo> for ServerConn {
+    type Result = MessageResult<GetInfo>;
+    fn handle(&mut self, _msg: GetInfo, _ctx: &mut Self::Context) -> Self::Result {
+        let state = if self.disconnect_handle.is_some() {
+            ConnectionState::Handshaking
+        } else {
+            ConnectionState::Connected
+        };
+        MessageResult(ConnectionInfo {
+            address: self.addr,
+            guid: self.guid,
+            worker: self.worker,
+            connected_since: self.connected_since,
+            state,
+            rtt: self.session.rtt(),
+            idle: self.session.idle(),
+            queue: self.session.queue_depth(),
+        })
     }
 }
 
 impl Handler<DisconnectConn> for ServerConn {
     type Result = ();
     fn handle(&mut self, _msg: DisconnectConn, _ctx: &mut Self::Context) -> Self::Result {
-        self.disconnect()
+        let _enter = self.span.clone().entered();
+        self.session.disconnect()
+    }
+}
+
+impl Handler<DisconnectNow> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, _msg: DisconnectNow, _ctx: &mut Self::Context) -> Self::Result {
+        let _enter = self.span.clone().entered();
+        self.session.disconnect_now()
+    }
+}
+
+impl Handler<SetNotices> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: SetNotices, _ctx: &mut Self::Context) -> Self::Result {
+        self.notices = msg.0;
+    }
+}
+
+impl Handler<SetLinger> for ServerConn {
+    type Result = ();
+    fn handle(&mut self, msg: SetLinger, _ctx: &mut Self::Context) -> Self::Result {
+        self.session.set_linger(msg.0);
     }
 }
 
@@ -364,14 +1411,230 @@ impl Handler<DisconnectConn> for ServerConn {
 #[rtype(result = "()")]
 pub(crate) struct SendPacket(BytesMut);
 
+#[derive(Message)]
+#[rtype(result = "Result<(), TrySendError>")]
+pub(crate) struct TrySendPacket(BytesMut);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct SendFrame {
+    payload: Bytes,
+    options: SendOptions,
+    receipt: Option<ReceiptSender>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct FlushConn;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct LimitConn(Option<RateLimit>);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct ShareBucket(Option<SharedBucket>);
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub(crate) struct DisconnectConn;
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct DisconnectNow;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct LingerConn(Duration);
+
+#[derive(Message)]
+#[rtype(result = "ConnectionInfo")]
+pub(crate) struct GetInfo;
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub(crate) struct ConnectionEnd(SocketAddr, u64);
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct HandshakeAccepted(SocketAddr);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ShardReceived(usize, UdpPacket);
+
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub(crate) struct ProveOwnership(u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct ReplaceConn;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct MigrateConn {
+    address: SocketAddr,
+    udp: Recipient<SendUdp>,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct SetMotd(pub String);
+
+#[derive(Clone, Message)]
+#[rtype(result = "()")]
+pub struct SetNotices(pub Option<Recipient<RakServerNotice>>);
+
+#[derive(Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct SetLinger(pub Option<Duration>);
+
+#[derive(Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct SetMigration(pub bool);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TakeoverPolicy {
+    Reject,
+    Replace,
+    RejectIfActive(Duration),
+}
+
+#[derive(Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct SetTakeoverPolicy(pub TakeoverPolicy);
+
+#[derive(Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct SetQueueLimits(pub QueueLimits);
+
+#[derive(Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct SetCoalescing(pub Coalescing);
+
+#[derive(Clone, Copy, Default, Message)]
+#[rtype(result = "()")]
+pub struct SetBandwidth {
+    pub per_connection: Option<RateLimit>,
+    pub global: Option<RateLimit>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Arc<ServerMetrics>")]
+pub struct GetMetrics;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Peer {
+    Guid(u64),
+    Address(SocketAddr),
+}
+
+#[derive(Clone, Debug)]
+pub enum BroadcastTarget {
+    All,
+    Only(Vec<Peer>),
+    Except(Vec<Peer>),
+}
+
+#[derive(Clone, Message)]
+#[rtype(result = "()")]
+pub struct Broadcast {
+    pub payload: Bytes,
+    pub target: BroadcastTarget,
+    pub options: SendOptions,
+}
+
+impl Broadcast {
+    pub fn new(payload: Bytes) -> Self {
+        Self {
+            payload,
+            target: BroadcastTarget::All,
+            options: SendOptions::new(),
+        }
+    }
+    pub fn only(mut self, peers: Vec<Peer>) -> Self {
+        self.target = BroadcastTarget::Only(peers);
+        self
+    }
+    pub fn except(mut self, peers: Vec<Peer>) -> Self {
+        self.target = BroadcastTarget::Except(peers);
+        self
+    }
+    pub fn reliability(mut self, reliability: Reliability) -> Self {
+        self.options = self.options.reliability(reliability);
+        self
+    }
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.options = self.options.channel(channel);
+        self
+    }
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.options = self.options.priority(priority);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Handshaking,
+    Connected,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    pub address: SocketAddr,
+    pub guid: u64,
+    pub worker: u32,
+    pub connected_since: Option<SystemTime>,
+    pub state: ConnectionState,
+    pub rtt: Option<Duration>,
+    pub idle: Duration,
+    pub queue: QueueDepth,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<ConnectionInfo>")]
+pub struct ListConnections;
+
+#[derive(Clone)]
+pub struct Affinity(Arc<dyn Fn(SocketAddr, u64) -> Option<u64> + Send + Sync>);
+
+impl Affinity {
+    pub fn new<F>(group: F) -> Self
+    where
+        F: Fn(SocketAddr, u64) -> Option<u64> + Send + Sync + 'static,
+    {
+        Self(Arc::new(group))
+    }
+}
+
+#[derive(Clone, Message)]
+#[rtype(result = "()")]
+pub struct SetAffinity(pub Option<Affinity>);
+
+#[derive(Clone, Debug)]
+pub struct WorkerInfo {
+    pub index: u32,
+    pub sessions: u32,
+    pub load: f64,
+    pub peers: Vec<SocketAddr>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<WorkerInfo>")]
+pub struct ListWorkers;
+
+#[derive(Message)]
+#[rtype(result = "Option<ConnectionHandle>")]
+pub struct FindConnection(pub Peer);
+
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct ConnectionCount;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Shutdown {
+    pub timeout: Duration,
+}
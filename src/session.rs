@@ -1,14 +1,25 @@
-use std::{net::SocketAddr, time::Instant};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use actix::{dev::ToEnvelope, prelude::*};
-use bytes::BytesMut;
+use bytes::Bytes;
 
 use crate::{
-    macros::unwrap_or_return,
+    macros::{debug, info, unwrap_or_return, warning},
+    metrics::ServerMetrics,
+    pacing::{self, SharedBucket, TokenBucket},
     packet::ACKQueue,
     packetqueue::PacketQueue,
     packets::*,
     receivedqueue::ReceivedQueue,
+    relay::{relay_guid, RELAY_MTU},
+    send::{
+        Coalescing, OverflowPolicy, Priority, QueueDepth, QueueLimits, RateLimit, ReceiptSender,
+        SendOptions, TrySendError,
+    },
     udp::{ReceivedUdp, SendUdp, UdpPacket},
 };
 
@@ -18,6 +29,23 @@ const ACK_FLAG: u8 = 0x40;
 
 const NACK_FLAG: u8 = 0x20;
 
+const NACK_BURST: usize = 16;
+
+const RELIABLE_HEADROOM: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+enum EndReason {
+    Timeout,
+    Remote,
+    Local,
+}
+
+enum SessionState {
+    Open,
+    Closing(Instant, EndReason),
+    Closed,
+}
+
 pub(crate) struct Session<M>
 where
     M: Actor,
@@ -34,11 +62,18 @@ where
     addr: SocketAddr,
     mtu: u16,
     message_index: u32,
-    order_index: u32,
+    order_index: [u32; ORDER_CHANNELS as usize],
+    sequence_index: [u32; ORDER_CHANNELS as usize],
     split_id: u16,
     last_ping: Instant,
     last_receive: Instant,
-    disconnected: bool,
+    rtt: Option<Duration>,
+    linger: Option<Duration>,
+    limits: QueueLimits,
+    bucket: Option<TokenBucket>,
+    global: Option<SharedBucket>,
+    state: SessionState,
+    metrics: Option<Arc<ServerMetrics>>,
 }
 
 impl<M> Session<M>
@@ -49,7 +84,17 @@ where
     <M as actix::Actor>::Context: ToEnvelope<M, ReceivedDatagram>,
     <M as actix::Actor>::Context: ToEnvelope<M, SessionEnd>,
 {
-    pub fn new(addr: SocketAddr, mtu: u16, udp: Recipient<SendUdp>, parent: Addr<M>) -> Self {
+    pub fn new(
+        addr: SocketAddr,
+        mtu: u16,
+        udp: Recipient<SendUdp>,
+        parent: Addr<M>,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Self {
+        let mtu = match relay_guid(&addr) {
+            Some(_) => mtu.min(RELAY_MTU),
+            None => mtu,
+        };
         Self {
             ack_queue: ACKQueue::new(),
             packet_queue: PacketQueue::new(mtu),
@@ -59,19 +104,32 @@ where
             addr,
             mtu,
             message_index: 0,
-            order_index: 0,
+            order_index: [0; ORDER_CHANNELS as usize],
+            sequence_index: [0; ORDER_CHANNELS as usize],
             split_id: 0,
             last_ping: Instant::now(),
             last_receive: Instant::now(),
-            disconnected: false,
+            rtt: None,
+            linger: None,
+            limits: QueueLimits::default(),
+            bucket: None,
+            global: None,
+            state: SessionState::Open,
+            metrics,
         }
     }
     pub fn update(&mut self) {
-        if !self.disconnected {
+        if let SessionState::Closing(deadline, reason) = self.state {
+            self.flush_queue();
+            self.flush_ack();
+            if self.packet_queue.is_empty() || Instant::now() >= deadline {
+                self.end(reason);
+            }
+        } else if let SessionState::Open = self.state {
             self.flush_queue();
             self.flush_ack();
             if Instant::now().duration_since(self.last_receive).as_secs() > 10 {
-                self.disconnect();
+                self.close(EndReason::Timeout);
             }
             if Instant::now().duration_since(self.last_ping).as_millis() > 3000 {
                 self.last_ping = Instant::now();
@@ -79,12 +137,38 @@ where
             }
         }
     }
-    pub fn force_flush(&mut self) {
-        self.flush_queue();
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+    pub fn flush(&mut self) {
+        self.packet_queue.flush();
+        self.flush_immediate();
     }
     fn flush_queue(&mut self) {
-        for send_able in self.packet_queue.get_packet() {
-            let frame_set = send_able.encode();
+        let bucket = &mut self.bucket;
+        let global = self.global.as_ref();
+        let send_able = self
+            .packet_queue
+            .get_packet(|x| pacing::admit(bucket, global, x));
+        let resent = self.packet_queue.take_resent();
+        if resent != 0 {
+            debug!(resent, "resending frame sets");
+            if let Some(metrics) = &self.metrics {
+                metrics.resent(resent);
+            }
+        }
+        self.send_frame_sets(send_able);
+    }
+    fn flush_immediate(&mut self) {
+        let bucket = &mut self.bucket;
+        let global = self.global.as_ref();
+        let send_able = self
+            .packet_queue
+            .take_sendable(|x| pacing::admit(bucket, global, x));
+        self.send_frame_sets(send_able);
+    }
+    fn send_frame_sets(&mut self, frame_sets: Vec<Bytes>) {
+        for frame_set in frame_sets {
             unwrap_or_return!(self.udp.do_send(SendUdp(UdpPacket {
                 bytes: frame_set,
                 addr: self.addr,
@@ -103,25 +187,38 @@ where
     fn send_ack(&mut self, packet: (u32, u32)) {
         let ack = Ack::new(packet);
         let buff = encode(ack);
+        self.charge(buff.len());
         unwrap_or_return!(self.udp.do_send(SendUdp(UdpPacket {
-            bytes: buff,
+            bytes: buff.freeze(),
             addr: self.addr,
         })));
     }
-    fn handle_ack(&mut self, buff: &BytesMut) {
+    fn handle_ack(&mut self, buff: &Bytes) {
         let ack = unwrap_or_return!(decode::<Ack>(buff));
         for sequence in ack.get_all() {
             self.packet_queue.received(sequence);
         }
     }
-    fn handle_nack(&mut self, buff: &BytesMut) {
+    fn handle_nack(&mut self, buff: &Bytes) {
         let nack = unwrap_or_return!(decode::<Nack>(buff));
-        for sequence in nack.get_all() {
+        let sequences = nack.get_all();
+        if sequences.len() >= NACK_BURST {
+            warning!(missing = sequences.len(), "nack burst");
+        } else {
+            debug!(missing = sequences.len(), "received nack");
+        }
+        for sequence in sequences {
             self.packet_queue.resend(sequence)
         }
     }
-    fn handle_datagram(&mut self, buff: &BytesMut) {
-        let frame_set = unwrap_or_return!(FrameSet::decode(buff));
+    fn handle_datagram(&mut self, buff: &Bytes) {
+        let frame_set = match FrameSet::decode_bytes(buff) {
+            Ok(frame_set) => frame_set,
+            Err(_e) => {
+                debug!(header = buff[0], len = buff.len(), error = %_e, "failed to decode frame set");
+                return;
+            }
+        };
         self.ack_queue.add(frame_set.sequence_number);
         for frame in frame_set.datas {
             self.receive_packet(frame)
@@ -129,8 +226,10 @@ where
     }
     fn send_nack(&mut self, miss: u32) {
         let nack = Nack::new((miss, miss));
+        let buff = encode(nack);
+        self.charge(buff.len());
         unwrap_or_return!(self.udp.do_send(SendUdp(UdpPacket {
-            bytes: encode(nack),
+            bytes: buff.freeze(),
             addr: self.addr,
         })));
     }
@@ -141,13 +240,8 @@ where
         self.send(frame);
     }
     fn receive_packet(&mut self, frame: Frame) {
-        if !frame.reliability.sequenced_or_ordered() {
-            self.handle_packet(frame);
-        } else {
-            self.received.add(frame);
-            for packet in self.received.get_all() {
-                self.handle_packet(packet);
-            }
+        for packet in self.received.add(frame) {
+            self.handle_packet(packet);
         }
     }
     fn handle_packet(&mut self, frame: Frame) {
@@ -157,11 +251,14 @@ where
             self.handle_connected_ping(ping);
             return;
         } else if frame.data[0] == ConnectedPong::ID {
-            let _pong = unwrap_or_return!(decode::<ConnectedPong>(&frame.data));
+            let pong = unwrap_or_return!(decode::<ConnectedPong>(&frame.data));
+            let rtt = (time() as i64 - pong.client_timestamp).max(0);
+            self.rtt = Some(Duration::from_millis(rtt as u64));
             return;
         } else if frame.data[0] == Disconnected::ID {
             let _disconnect = unwrap_or_return!(decode::<Disconnected>(&frame.data));
-            self.end();
+            self.flush_ack();
+            self.end(EndReason::Remote);
             return;
         }
         self.parent.do_send(ReceivedDatagram(frame));
@@ -184,73 +281,217 @@ where
         let buff = encode(pong);
 
         let frame = Frame::new(Reliability::Unreliable, buff);
-        self.send(frame);
+        self.send_tracked(frame, None, Priority::Immediate);
+        self.flush_immediate();
     }
     fn send(&mut self, packet: Frame) {
-        self.packet_queue.add_frame(packet);
+        self.send_tracked(packet, None, Priority::Medium);
+    }
+    fn send_tracked(&mut self, mut packet: Frame, receipt: Option<u32>, priority: Priority) {
+        if !matches!(self.state, SessionState::Open) {
+            return;
+        }
+        if packet.reliability.reliable() {
+            packet.message_index = self.message_index;
+            self.message_index += 1;
+        }
+        self.packet_queue.add_frame(packet, receipt, priority);
+    }
+    pub fn set_coalescing(&mut self, coalescing: Coalescing) {
+        self.packet_queue.set_coalescing(coalescing);
+    }
+    pub fn set_bandwidth(&mut self, limit: Option<RateLimit>) {
+        self.bucket = limit.map(TokenBucket::new);
+    }
+    pub fn set_global_bandwidth(&mut self, global: Option<SharedBucket>) {
+        self.global = global;
+    }
+    pub fn rebind(&mut self, addr: SocketAddr, udp: Recipient<SendUdp>) {
+        self.addr = addr;
+        self.udp = udp;
+        self.last_receive = Instant::now();
+    }
+    pub fn idle(&self) -> Duration {
+        self.last_receive.elapsed()
+    }
+    pub fn mtu(&self) -> u16 {
+        self.mtu
+    }
+    fn charge(&mut self, bytes: usize) {
+        let mut global = self.global.as_ref().map(|x| x.lock().unwrap());
+        pacing::charge(&mut self.bucket, global.as_deref_mut(), bytes);
+    }
+    pub fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.limits = limits;
+    }
+    pub fn queue_depth(&self) -> QueueDepth {
+        self.packet_queue.depth()
+    }
+    fn admit(&mut self, len: usize, reliability: &Reliability) -> Result<(), TrySendError> {
+        if !matches!(self.state, SessionState::Open) {
+            return Err(TrySendError::Closed);
+        }
+        let depth = self.packet_queue.depth();
+        if depth.bytes.saturating_add(len) <= self.limits.max_bytes
+            && depth.frames < self.limits.max_frames
+        {
+            return Ok(());
+        }
+        match self.limits.policy {
+            OverflowPolicy::Reject => Err(TrySendError::QueueFull),
+            // reliable frames may overshoot the limits, but only up to a hard ceiling
+            OverflowPolicy::DropUnreliable
+                if reliability.reliable()
+                    && depth.bytes.saturating_add(len)
+                        <= self.limits.max_bytes.saturating_mul(RELIABLE_HEADROOM)
+                    && depth.frames < self.limits.max_frames.saturating_mul(RELIABLE_HEADROOM) =>
+            {
+                Ok(())
+            }
+            OverflowPolicy::DropUnreliable => Err(TrySendError::QueueFull),
+            OverflowPolicy::Disconnect => {
+                warning!(
+                    frames = depth.frames,
+                    bytes = depth.bytes,
+                    "send queue overflow, disconnecting"
+                );
+                self.disconnect_now();
+                Err(TrySendError::Closed)
+            }
+        }
+    }
+    pub fn set_linger(&mut self, linger: Option<Duration>) {
+        self.linger = linger;
     }
     pub fn disconnect(&mut self) {
+        match self.linger {
+            Some(timeout) => self.linger(timeout),
+            None => self.close(EndReason::Local),
+        }
+    }
+    pub fn disconnect_now(&mut self) {
+        self.close(EndReason::Local);
+    }
+    pub fn linger(&mut self, timeout: Duration) {
+        if !matches!(self.state, SessionState::Open) {
+            return;
+        }
+        self.queue_disconnect();
+        self.flush();
+        self.state = SessionState::Closing(Instant::now() + timeout, EndReason::Local);
+    }
+    fn close(&mut self, reason: EndReason) {
+        match self.state {
+            SessionState::Open => self.queue_disconnect(),
+            SessionState::Closing(..) => {}
+            SessionState::Closed => return,
+        }
+        self.flush();
+        self.end(reason);
+    }
+    fn queue_disconnect(&mut self) {
         let buff = encode(Disconnected {});
         let mut frame = Frame::new(Reliability::ReliableOrdered, buff);
-        frame.message_index = self.message_index;
-        frame.order_index = self.order_index;
-        self.message_index += 1;
-        self.order_index += 1;
+        self.assign_indices(&mut frame, 0);
         self.send(frame);
-        self.flush_queue();
-        self.end();
     }
-    fn end(&mut self) {
+    fn end(&mut self, _reason: EndReason) {
+        if let SessionState::Closed = self.state {
+            return;
+        }
+        info!(reason = ?_reason, "session closed");
+        self.packet_queue.abandon();
         self.parent.do_send(SessionEnd);
-        self.disconnected = true;
+        self.state = SessionState::Closed;
     }
     pub fn send_system_packet<P: Packet>(&mut self, packet: P, reliability: Reliability) {
         let buff = encode(packet);
-        let mut packet = Frame::new(reliability.clone(), buff);
-        if reliability.reliable() {
-            packet.message_index = self.message_index;
-            self.message_index += 1;
+        let mut packet = Frame::new(reliability, buff);
+        self.assign_indices(&mut packet, 0);
+        self.send(packet);
+    }
+    fn assign_indices(&mut self, frame: &mut Frame, channel: u8) {
+        let index = channel as usize;
+        if frame.reliability.sequenced() {
+            frame.sequence_index = self.sequence_index[index];
+            frame.order_index = self.order_index[index];
+            self.sequence_index[index] += 1;
+        } else if frame.reliability.sequenced_or_ordered() {
+            frame.order_index = self.order_index[index];
+            self.order_index[index] += 1;
         }
-        if reliability.sequenced_or_ordered() {
-            packet.order_index = self.order_index;
-            self.order_index += 1;
+        frame.order_channel = channel;
+    }
+    pub fn send_to(&mut self, buff: Bytes) {
+        self.send_with(buff, &SendOptions::new());
+    }
+    pub fn send_with(&mut self, buff: Bytes, options: &SendOptions) {
+        if let Err(_e) = self.try_send_with(buff, options) {
+            debug!(error = %_e, "dropped outgoing packet");
         }
-        self.send(packet);
     }
-    pub fn send_to(&mut self, mut buff: BytesMut) {
+    pub fn try_send_with(
+        &mut self,
+        buff: Bytes,
+        options: &SendOptions,
+    ) -> Result<(), TrySendError> {
+        self.admit(buff.len(), &options.reliability)?;
+        self.frame(buff, options, None);
+        Ok(())
+    }
+    pub fn send_with_receipt(
+        &mut self,
+        buff: Bytes,
+        options: &SendOptions,
+        receipt: ReceiptSender,
+    ) {
+        if let Err(_e) = self.admit(buff.len(), &options.reliability) {
+            debug!(error = %_e, receipt = receipt.id, "dropped outgoing packet");
+            return;
+        }
+        let id = receipt.id;
+        self.packet_queue.track(receipt);
+        self.frame(buff, options, Some(id));
+    }
+    fn frame(&mut self, mut buff: Bytes, options: &SendOptions, receipt: Option<u32>) {
+        // the field is public, so clamp here as well as in the builder
+        let channel = options.channel.min(ORDER_CHANNELS - 1);
+        let priority = options.priority;
         if buff.len() < (self.mtu - 14 - 32).into() {
-            let mut frame = Frame::new(Reliability::ReliableOrdered, buff);
-            frame.message_index = self.message_index;
-            frame.order_index = self.order_index;
-            self.message_index += 1;
-            self.order_index += 1;
-            self.send(frame);
+            let mut frame = Frame::new(options.reliability.clone(), buff);
+            self.assign_indices(&mut frame, channel);
+            self.send_tracked(frame, receipt, priority);
         } else {
             let len = buff.len() as u16;
             let max = self.mtu - 24 - 32 - 5;
             let mut split_len = buff.len() as u16 / max;
-            if len % max != 0 {
+            if !len.is_multiple_of(max) {
                 split_len += 1;
             }
+            let reliability = match options.reliability {
+                Reliability::Unreliable => Reliability::Reliable,
+                Reliability::UnreliableSequenced => Reliability::ReliableSequenced,
+                Reliability::UnreliableWithAckReceipt => Reliability::ReliableWithAckReceipt,
+                ref reliability => reliability.clone(),
+            };
+            let mut header = Frame::new(reliability, Bytes::new());
+            self.assign_indices(&mut header, channel);
             for i in 0..split_len {
-                let pos;
-                if i == split_len - 1 {
-                    pos = len % max;
-                } else {
-                    pos = max;
-                }
-                let mut frame = Frame::new(Reliability::ReliableOrdered, buff.split_to(pos.into()));
+                let pos = buff.len().min(max.into());
+                let mut frame = header.clone();
+                frame.data = buff.split_to(pos);
                 frame.split = true;
-                frame.message_index = self.message_index;
-                frame.order_index = self.order_index;
                 frame.split_count = split_len as u32;
                 frame.split_id = self.split_id;
                 frame.split_index = i as u32;
-                self.send(frame);
-                self.message_index += 1;
+                self.send_tracked(frame, receipt, priority);
             }
             self.split_id += 1;
-            self.order_index += 1;
+        }
+        if let Priority::Immediate = priority {
+            self.flush_immediate();
+        } else if self.packet_queue.should_flush() {
+            self.flush();
         }
     }
 }
@@ -269,3 +510,14 @@ pub(crate) fn time() -> u128 {
         .unwrap()
         .as_millis()
 }
+
+pub(crate) fn random() -> u64 {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+    };
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(time());
+    hasher.finish()
+}
@@ -0,0 +1,252 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use actix::{prelude::*, WeakAddr};
+use bytes::Bytes;
+
+use crate::{
+    capture::{Capture, SetCapture},
+    macros::unwrap_or_return,
+    metrics::ServerMetrics,
+    udp::{ReceivedUdp, SendUdp, UdpPacket},
+};
+
+const EPHEMERAL_PORT: u16 = 49152;
+
+const NAT_PORT: u16 = 20000;
+
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inner: Arc<Mutex<NetworkInner>>,
+}
+
+#[derive(Default)]
+struct NetworkInner {
+    endpoints: HashMap<SocketAddr, WeakAddr<MemorySocket>>,
+    next_port: u16,
+    nats: HashMap<IpAddr, Nat>,
+    mapped: HashMap<SocketAddr, SocketAddr>,
+}
+
+struct Nat {
+    public: IpAddr,
+    symmetric: bool,
+    next_port: u16,
+    mappings: HashMap<(SocketAddr, Option<SocketAddr>), SocketAddr>,
+    allowed: HashSet<(SocketAddr, SocketAddr)>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn bind(
+        &self,
+        mut local: SocketAddr,
+        handler: Recipient<ReceivedUdp>,
+        arbiter: &Arbiter,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Addr<MemorySocket> {
+        let mut inner = self.inner.lock().unwrap();
+        if local.port() == 0 {
+            loop {
+                let port = EPHEMERAL_PORT.wrapping_add(inner.next_port);
+                inner.next_port = inner.next_port.wrapping_add(1);
+                local.set_port(port);
+                if !inner.bound(&local) {
+                    break;
+                }
+            }
+        }
+        let network = self.clone();
+        let socket = MemorySocket::start_in_arbiter(arbiter, move |_ctx| MemorySocket {
+            network,
+            local,
+            handler,
+            capture: None,
+            metrics,
+        });
+        inner.endpoints.insert(local, socket.downgrade());
+        socket
+    }
+
+    pub fn is_bound(&self, address: SocketAddr) -> bool {
+        self.inner.lock().unwrap().bound(&address)
+    }
+
+    pub fn unbind(&self, address: SocketAddr) {
+        self.inner.lock().unwrap().endpoints.remove(&address);
+    }
+
+    pub fn rebind(&self, from: SocketAddr, to: SocketAddr) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.bound(&to) {
+            return false;
+        }
+        match inner.endpoints.remove(&from).and_then(|x| x.upgrade()) {
+            Some(endpoint) => {
+                inner.endpoints.insert(to, endpoint.downgrade());
+                endpoint.do_send(Rebind(to));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_nat(&self, private: IpAddr, public: IpAddr) {
+        self.nat(private, public, false)
+    }
+
+    pub fn add_symmetric_nat(&self, private: IpAddr, public: IpAddr) {
+        self.nat(private, public, true)
+    }
+
+    fn nat(&self, private: IpAddr, public: IpAddr, symmetric: bool) {
+        self.inner.lock().unwrap().nats.insert(
+            private,
+            Nat {
+                public,
+                symmetric,
+                next_port: NAT_PORT,
+                mappings: HashMap::new(),
+                allowed: HashSet::new(),
+            },
+        );
+    }
+
+    pub fn public_address(&self, private: SocketAddr) -> Option<SocketAddr> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .nats
+            .get(&private.ip())?
+            .mappings
+            .get(&(private, None))
+            .copied()
+    }
+
+    pub fn inject<B: Into<Bytes>>(
+        &self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        bytes: B,
+    ) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let source = inner.outbound(source, destination);
+        let destination = match inner.inbound(source, destination) {
+            Some(destination) => destination,
+            None => return false,
+        };
+        let endpoint = inner.endpoints.get(&destination).and_then(|x| x.upgrade());
+        drop(inner);
+        match endpoint {
+            Some(endpoint) => {
+                endpoint.do_send(ReceivedUdp(UdpPacket {
+                    bytes: bytes.into(),
+                    addr: source,
+                }));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl NetworkInner {
+    fn bound(&self, address: &SocketAddr) -> bool {
+        self.endpoints
+            .get(address)
+            .map(|x| x.upgrade().is_some())
+            .unwrap_or(false)
+    }
+
+    fn outbound(&mut self, source: SocketAddr, destination: SocketAddr) -> SocketAddr {
+        let nat = match self.nats.get_mut(&source.ip()) {
+            Some(nat) => nat,
+            None => return source,
+        };
+        let key = (source, nat.symmetric.then_some(destination));
+        let public = match nat.mappings.get(&key) {
+            Some(public) => *public,
+            None => {
+                let public = SocketAddr::new(nat.public, nat.next_port);
+                nat.next_port = nat.next_port.wrapping_add(1);
+                nat.mappings.insert(key, public);
+                self.mapped.insert(public, source);
+                public
+            }
+        };
+        nat.allowed.insert((public, destination));
+        public
+    }
+
+    fn inbound(&self, source: SocketAddr, destination: SocketAddr) -> Option<SocketAddr> {
+        match self.mapped.get(&destination) {
+            Some(private) => self.nats[&private.ip()]
+                .allowed
+                .contains(&(destination, source))
+                .then_some(*private),
+            None if self.nats.values().any(|x| x.public == destination.ip()) => None,
+            None => Some(destination),
+        }
+    }
+}
+
+pub(crate) struct MemorySocket {
+    network: MemoryNetwork,
+    local: SocketAddr,
+    handler: Recipient<ReceivedUdp>,
+    capture: Option<Capture>,
+    metrics: Option<Arc<ServerMetrics>>,
+}
+
+impl Actor for MemorySocket {
+    type Context = Context<Self>;
+}
+
+impl Handler<ReceivedUdp> for MemorySocket {
+    type Result = ();
+    fn handle(&mut self, msg: ReceivedUdp, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(capture) = &self.capture {
+            capture.record(msg.0.addr, self.local, &msg.0.bytes);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.received(msg.0.bytes.len());
+        }
+        unwrap_or_return!(self.handler.do_send(msg));
+    }
+}
+
+impl Handler<SendUdp> for MemorySocket {
+    type Result = ();
+    fn handle(&mut self, msg: SendUdp, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(capture) = &self.capture {
+            capture.record(self.local, msg.0.addr, &msg.0.bytes);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.sent(msg.0.bytes.len());
+        }
+        self.network.inject(self.local, msg.0.addr, msg.0.bytes);
+    }
+}
+
+impl Handler<Rebind> for MemorySocket {
+    type Result = ();
+    fn handle(&mut self, msg: Rebind, _ctx: &mut Self::Context) -> Self::Result {
+        self.local = msg.0;
+    }
+}
+
+impl Handler<SetCapture> for MemorySocket {
+    type Result = ();
+    fn handle(&mut self, msg: SetCapture, _ctx: &mut Self::Context) -> Self::Result {
+        self.capture = msg.0;
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Rebind(SocketAddr);
@@ -1,18 +1,26 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use actix::{dev::ToEnvelope, prelude::*};
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use tokio::{
     net::udp::{RecvHalf, SendHalf},
     sync::Mutex,
 };
 
-use crate::macros::unwrap_or_return;
+use crate::{
+    capture::{Capture, SetCapture},
+    macros::{unwrap_or_return, warning},
+    metrics::ServerMetrics,
+};
+
+const MAX_DATAGRAM: usize = 2048;
+
+const RECV_BUFFER: usize = 64 * 1024;
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct UdpPacket {
-    pub bytes: BytesMut,
+    pub bytes: Bytes,
     pub addr: SocketAddr,
 }
 
@@ -28,6 +36,31 @@ pub struct ReceivedUdp(pub UdpPacket);
 #[rtype(result = "()")]
 pub struct SendUdp(pub UdpPacket);
 
+#[derive(Clone)]
+pub(crate) struct Transport {
+    pub send: Recipient<SendUdp>,
+    pub capture: Recipient<SetCapture>,
+}
+
+impl Transport {
+    pub fn new<A>(addr: Addr<A>) -> Self
+    where
+        A: Actor + Handler<SendUdp> + Handler<SetCapture>,
+        A::Context: ToEnvelope<A, SendUdp> + ToEnvelope<A, SetCapture>,
+    {
+        Self {
+            send: addr.clone().recipient(),
+            capture: addr.recipient(),
+        }
+    }
+    pub fn send_to(&self, bytes: BytesMut, addr: SocketAddr) {
+        unwrap_or_return!(self.send.do_send(SendUdp(UdpPacket {
+            bytes: bytes.freeze(),
+            addr
+        })));
+    }
+}
+
 pub struct UdpActor<T>
 where
     T: Actor,
@@ -38,6 +71,9 @@ where
     receiver: Option<RecvHalf>,
     recv_handle: Option<SpawnHandle>,
     handler: Addr<T>,
+    metrics: Option<Arc<ServerMetrics>>,
+    local: SocketAddr,
+    capture: Option<Capture>,
 }
 
 impl<T> UdpActor<T>
@@ -46,14 +82,49 @@ where
     T: Handler<ReceivedUdp>,
     <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
 {
-    pub fn new(socket: tokio::net::UdpSocket, handler: Addr<T>, arbiter: &Arbiter) -> Addr<Self> {
+    pub fn new(
+        socket: tokio::net::UdpSocket,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Addr<Self> {
+        let local = socket
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        Self::start_in_arbiter(arbiter, move |_ctx| {
+            Self::with_socket(socket, local, handler, metrics)
+        })
+    }
+    pub fn from_std(
+        socket: std::net::UdpSocket,
+        handler: Addr<T>,
+        arbiter: &Arbiter,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Addr<Self> {
+        let local = socket
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        Self::start_in_arbiter(arbiter, move |_ctx| {
+            let socket = tokio::net::UdpSocket::from_std(socket).expect("register udp socket");
+            Self::with_socket(socket, local, handler, metrics)
+        })
+    }
+    fn with_socket(
+        socket: tokio::net::UdpSocket,
+        local: SocketAddr,
+        handler: Addr<T>,
+        metrics: Option<Arc<ServerMetrics>>,
+    ) -> Self {
         let (r, s) = socket.split();
-        Self::start_in_arbiter(arbiter, |_ctx| Self {
+        Self {
             sender: Arc::new(Mutex::new(s)),
             receiver: Some(r),
             recv_handle: None,
             handler,
-        })
+            metrics,
+            local,
+            capture: None,
+        }
     }
 }
 
@@ -68,9 +139,12 @@ where
         let mut r = self.receiver.take().unwrap();
         let address = ctx.address();
         let udp_future = async move {
+            let mut buff = BytesMut::new();
             loop {
-                let mut buff = BytesMut::with_capacity(2048);
-                buff.resize(2048, 0);
+                if buff.capacity() < MAX_DATAGRAM {
+                    buff.reserve(RECV_BUFFER);
+                }
+                buff.resize(MAX_DATAGRAM, 0);
                 let (len, source) = match r.recv_from(&mut buff).await {
                     Ok(p) => p,
                     Err(e) => {
@@ -81,10 +155,10 @@ where
                         }
                     }
                 };
+                buff.truncate(len);
                 if len != 0 {
-                    buff.truncate(len);
                     address.do_send(UdpPacket {
-                        bytes: buff,
+                        bytes: buff.split().freeze(),
                         addr: source,
                     });
                 }
@@ -107,6 +181,12 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: UdpPacket, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(capture) = &self.capture {
+            capture.record(msg.addr, self.local, &msg.bytes);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.received(msg.bytes.len());
+        }
         self.handler.do_send(ReceivedUdp(msg))
     }
 }
@@ -118,7 +198,12 @@ where
     <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
 {
     type Result = bool;
-    fn handle(&mut self, _msg: SocketErr, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: SocketErr, _ctx: &mut Self::Context) -> Self::Result {
+        let SocketErr(_e) = msg;
+        warning!(error = %_e, "udp socket error");
+        if let Some(metrics) = &self.metrics {
+            metrics.socket_error();
+        }
         true
     }
 }
@@ -131,9 +216,27 @@ where
 {
     type Result = ();
     fn handle(&mut self, msg: SendUdp, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(capture) = &self.capture {
+            capture.record(self.local, msg.0.addr, &msg.0.bytes);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.sent(msg.0.bytes.len());
+        }
         let sender = self.sender.clone();
         tokio::spawn(async move {
             unwrap_or_return!(sender.lock().await.send_to(&msg.0.bytes, &msg.0.addr).await);
         });
     }
 }
+
+impl<T> Handler<SetCapture> for UdpActor<T>
+where
+    T: Actor,
+    T: Handler<ReceivedUdp>,
+    <T as actix::Actor>::Context: ToEnvelope<T, ReceivedUdp>,
+{
+    type Result = ();
+    fn handle(&mut self, msg: SetCapture, _ctx: &mut Self::Context) -> Self::Result {
+        self.capture = msg.0;
+    }
+}
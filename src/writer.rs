@@ -80,6 +80,7 @@ impl<'a> Writer<'a> {
             self.write_u8(0xff - ip_bytes[3]);
             self.write_u16(address.port(), Endian::Big)
         } else {
+            self.write_u8(0x6);
             self.write_u16(23, Endian::Little);
             self.write_u16(address.port(), Endian::Big);
             self.write_u32(0, Endian::Big);
@@ -0,0 +1,128 @@
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{RakClient, RakClientEvent},
+    send::{OverflowPolicy, QueueLimits, TrySendError},
+    server::{RakServer, RakServerEvent, SetQueueLimits},
+    transport::MemoryNetwork,
+};
+use bytes::BytesMut;
+
+struct Client;
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, _msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+struct Server {
+    network: MemoryNetwork,
+    rak_server: Addr<RakServer<Self>>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Connected(handle) => {
+                self.network.unbind(handle.address);
+                let rak_server = self.rak_server.clone();
+                async move {
+                    for _ in 0..4 {
+                        handle
+                            .try_send(BytesMut::from(&[0; 1000][..]))
+                            .await
+                            .unwrap();
+                    }
+                    let err = handle.try_send(BytesMut::from(&[0; 1000][..])).await;
+                    assert_eq!(err, Err(TrySendError::QueueFull));
+                    assert!(handle.queue_depth().await.unwrap().frames >= 4);
+
+                    rak_server
+                        .send(SetQueueLimits(QueueLimits::new(
+                            4096,
+                            usize::MAX,
+                            OverflowPolicy::DropUnreliable,
+                        )))
+                        .await
+                        .unwrap();
+                    let mut accepted = 0;
+                    while handle
+                        .try_send(BytesMut::from(&[0; 1000][..]))
+                        .await
+                        .is_ok()
+                    {
+                        accepted += 1;
+                        assert!(accepted < 64, "reliable sends are unbounded");
+                    }
+                    assert!(accepted >= 8);
+                    assert!(handle.queue_depth().await.unwrap().bytes <= 4 * 4096);
+
+                    rak_server
+                        .send(SetQueueLimits(QueueLimits::new(
+                            4096,
+                            usize::MAX,
+                            OverflowPolicy::Disconnect,
+                        )))
+                        .await
+                        .unwrap();
+                    let err = handle.try_send(BytesMut::from(&[0; 1000][..])).await;
+                    assert_eq!(err, Err(TrySendError::Closed));
+                }
+                .into_actor(self)
+                .wait(ctx);
+            }
+            RakServerEvent::Disconnected(_, _) => System::current().stop(),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn backpressure() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:0".parse().unwrap();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "backpressure".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetQueueLimits(QueueLimits::new(
+                4096,
+                usize::MAX,
+                OverflowPolicy::Reject,
+            )));
+            Server {
+                network: server_network,
+                rak_server,
+            }
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            Client
+        });
+    })
+    .unwrap();
+}
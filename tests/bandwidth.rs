@@ -0,0 +1,263 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{RakClient, RakClientEvent},
+    packets::{encode, Ack, Reliability},
+    send::{Priority, RateLimit, SendOptions},
+    server::{RakServer, RakServerEvent, SetBandwidth},
+    transport::MemoryNetwork,
+};
+use bytes::{Bytes, BytesMut};
+
+const BYTES: usize = 20_000;
+const CHUNK: usize = 1000;
+
+#[derive(Default)]
+struct Progress {
+    started: Option<Instant>,
+    received: usize,
+}
+
+struct Client {
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(bytes) = msg {
+            let mut progress = self.progress.lock().unwrap();
+            progress.received += bytes.len();
+            if progress.received == BYTES {
+                let elapsed = progress.started.unwrap().elapsed();
+                assert!(elapsed >= Duration::from_millis(700), "{:?}", elapsed);
+                System::current().stop();
+            }
+        }
+    }
+}
+
+struct Server {
+    clients: usize,
+    progress: Arc<Mutex<Progress>>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            self.progress
+                .lock()
+                .unwrap()
+                .started
+                .get_or_insert_with(Instant::now);
+            for _ in 0..BYTES / CHUNK / self.clients {
+                handle.send(BytesMut::from(&[0xfe; CHUNK][..]));
+            }
+        }
+    }
+}
+
+fn paced(bandwidth: SetBandwidth, clients: usize) {
+    System::run(move || {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let server_network = network.clone();
+        let server_progress = progress.clone();
+        Server::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "bandwidth".to_owned(),
+                ctx.address(),
+                2,
+            );
+            rak_server.do_send(bandwidth);
+            Server {
+                clients,
+                progress: server_progress,
+            }
+        });
+        for guid in 0..clients as u64 {
+            let network = network.clone();
+            let progress = progress.clone();
+            Client::create(move |ctx| {
+                let rak_client = RakClient::init_in_memory(
+                    &network,
+                    "10.0.0.2:0".parse().unwrap(),
+                    guid,
+                    ctx.address(),
+                    System::current().arbiter(),
+                );
+                rak_client.connect(server_addr);
+                Client { progress }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn per_connection_bandwidth() {
+    paced(
+        SetBandwidth {
+            per_connection: Some(RateLimit::new(20_000, 2_000)),
+            global: None,
+        },
+        1,
+    );
+}
+
+#[test]
+fn global_bandwidth() {
+    paced(
+        SetBandwidth {
+            per_connection: None,
+            global: Some(RateLimit::new(20_000, 2_000)),
+        },
+        2,
+    );
+}
+
+const LOW: u8 = 0xf0;
+const HIGH: u8 = 0xf2;
+
+struct Recorder {
+    received: Arc<Mutex<Vec<u8>>>,
+    expected: usize,
+}
+
+impl Actor for Recorder {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Recorder {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(bytes) = msg {
+            let mut received = self.received.lock().unwrap();
+            received.push(bytes[0]);
+            if received.len() == self.expected {
+                System::current().stop();
+            }
+        }
+    }
+}
+
+struct Sender {
+    network: MemoryNetwork,
+    server_addr: SocketAddr,
+    client_addr: SocketAddr,
+    forge_ack: bool,
+}
+
+impl Actor for Sender {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Sender {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            let options = SendOptions::new().reliability(Reliability::Reliable);
+            for _ in 0..8 {
+                handle.send_with(
+                    Bytes::from(vec![LOW; CHUNK]),
+                    options.clone().priority(Priority::Low),
+                );
+            }
+            if self.forge_ack {
+                // acknowledges sequences the pacer has not released yet
+                let network = self.network.clone();
+                let (client_addr, server_addr) = (self.client_addr, self.server_addr);
+                ctx.run_later(Duration::from_millis(100), move |_, _| {
+                    let ack = encode(Ack::new((0, 1000)));
+                    network.inject(client_addr, server_addr, ack);
+                });
+            } else {
+                ctx.run_later(Duration::from_millis(200), move |_, _| {
+                    handle.send_with(
+                        Bytes::from(vec![HIGH; CHUNK]),
+                        options.priority(Priority::High),
+                    );
+                });
+            }
+        }
+    }
+}
+
+fn paced_backlog(forge_ack: bool) -> Vec<u8> {
+    let received = Arc::new(Mutex::new(vec![]));
+    let result = received.clone();
+    System::run(move || {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let server_network = network.clone();
+        Sender::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "bandwidth".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetBandwidth {
+                per_connection: Some(RateLimit::new(5_000, 1_000)),
+                global: None,
+            });
+            Sender {
+                network: server_network,
+                server_addr,
+                client_addr,
+                forge_ack,
+            }
+        });
+        Recorder::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            ctx.run_later(Duration::from_secs(5), |_, _| System::current().stop());
+            Recorder {
+                received,
+                expected: if forge_ack { 8 } else { 9 },
+            }
+        });
+    })
+    .unwrap();
+    let received = result.lock().unwrap();
+    received.clone()
+}
+
+#[test]
+fn ack_for_unsent_sequence() {
+    assert_eq!(paced_backlog(true), vec![LOW; 8]);
+}
+
+#[test]
+fn high_overtakes_paced_backlog() {
+    let received = paced_backlog(false);
+    assert_eq!(received.len(), 9);
+    let high = received.iter().position(|x| *x == HIGH).unwrap();
+    assert!(high < 4, "{:?}", received);
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "batch-io", target_os = "linux"))]
+
+use std::net::{SocketAddr, UdpSocket};
+
+use actix::prelude::*;
+use actix_raknet::{
+    batch::BatchConfig,
+    client::{RakClient, RakClientEvent},
+    server::{RakServer, RakServerEvent},
+};
+use bytes::BytesMut;
+
+const PACKETS: usize = 64;
+
+struct Client {
+    received: usize,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(bytes) = msg {
+            assert_eq!(bytes.len(), 1000);
+            self.received += 1;
+            if self.received == PACKETS {
+                System::current().stop();
+            }
+        }
+    }
+}
+
+struct Server;
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            for _ in 0..PACKETS {
+                handle.send(BytesMut::from(&[0xfe; 1000][..]));
+            }
+        }
+    }
+}
+
+#[test]
+fn batched_transport() {
+    System::run(|| {
+        let server_socket = UdpSocket::bind("127.0.0.1:19150").unwrap();
+        let server_addr: SocketAddr = server_socket.local_addr().unwrap();
+        Server::create(move |ctx| {
+            RakServer::new_batched(
+                server_socket,
+                0x1919,
+                "batch".to_owned(),
+                ctx.address(),
+                1,
+                BatchConfig::new(16, true),
+            )
+            .unwrap();
+            Server
+        });
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_batched(
+                client_socket,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+                BatchConfig::default(),
+            )
+            .unwrap();
+            rak_client.connect(server_addr);
+            Client { received: 0 }
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,107 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::RakClientEvent,
+    packets::{Reliability, ORDER_CHANNELS},
+    send::SendOptions,
+    server::{Broadcast, Peer, RakServer, RakServerEvent},
+};
+use bytes::Bytes;
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client_with, Client, Connect};
+
+const CLIENTS: usize = 3;
+const EXCLUDED: u64 = 1;
+
+struct Server {
+    rak_server: Addr<RakServer<Self>>,
+    greeted: usize,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, _) = msg {
+            self.greeted += 1;
+            if self.greeted == CLIENTS {
+                let payload = Bytes::from(vec![0x19; 4000]);
+                self.rak_server.do_send(
+                    Broadcast::new(payload)
+                        .except(vec![Peer::Guid(EXCLUDED)])
+                        .channel(3),
+                );
+                self.rak_server.do_send(
+                    Broadcast::new(Bytes::from_static(b"only you"))
+                        .only(vec![Peer::Guid(EXCLUDED)])
+                        .reliability(Reliability::Reliable)
+                        .channel(u8::MAX),
+                );
+            }
+        }
+    }
+}
+
+async fn create_client(guid: u64, addr: SocketAddr, received: Arc<AtomicUsize>) -> Addr<Client> {
+    create_client_with(guid, addr, move |msg| {
+        if let RakClientEvent::Packet(bytes) = msg {
+            if guid == EXCLUDED {
+                assert_eq!(&bytes[..], b"only you");
+            } else {
+                assert_eq!(bytes.len(), 4000);
+                assert!(bytes.iter().all(|x| *x == 0x19));
+            }
+            if received.fetch_add(1, Ordering::SeqCst) + 1 == CLIENTS {
+                System::current().stop();
+            }
+        }
+    })
+    .await
+}
+
+async fn create_server(guid: u64, addr: SocketAddr, motd: String) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 2);
+        Server {
+            rak_server,
+            greeted: 0,
+        }
+    })
+}
+
+#[test]
+fn broadcast() {
+    System::run(||{
+        let server_addr: SocketAddr = "127.0.0.1:19146".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()));
+
+        let received = Arc::new(AtomicUsize::new(0));
+        for guid in 1..=CLIENTS as u64 {
+            let client_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            let client = block_on(create_client(guid, client_addr, received.clone()));
+            client.do_send(Connect(server_addr));
+        }
+    }).unwrap();
+}
+
+#[test]
+fn order_channel_clamped() {
+    assert_eq!(
+        SendOptions::new().channel(u8::MAX).channel,
+        ORDER_CHANNELS - 1
+    );
+    assert_eq!(SendOptions::new().channel(3).channel, 3);
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::{
+    capture::{Capture, PcapReader, Replay, SetCapture},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client, Connect};
+
+struct Server {
+    capture: Capture,
+    path: std::path::PathBuf,
+    server_addr: SocketAddr,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, _) = msg {
+            self.capture.flush().unwrap();
+            let datagrams = PcapReader::open(&self.path).unwrap().datagrams().unwrap();
+            assert!(datagrams
+                .iter()
+                .any(|x| x.source == self.server_addr && x.payload[0] == 0x06));
+
+            let network = MemoryNetwork::new();
+            Replayed::create(|ctx| {
+                RakServer::new_in_memory(
+                    &network,
+                    self.server_addr,
+                    0x1919,
+                    "replay".to_owned(),
+                    ctx.address(),
+                    1,
+                );
+                Replayed
+            });
+            let replay = Replay::new(datagrams);
+            let server_addr = self.server_addr;
+            actix::spawn(async move {
+                replay.run(network, server_addr, false).await;
+            });
+        }
+    }
+}
+
+struct Replayed;
+impl Actor for Replayed {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Replayed {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, bytes) = msg {
+            assert_eq!(&bytes[..], b"Hello Server!");
+            System::current().stop();
+        }
+    }
+}
+
+async fn create_server(guid: u64, addr: SocketAddr, motd: String) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    let path = std::env::temp_dir().join("actix-raknet-capture.pcap");
+    let capture = Capture::create(&path).unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 1);
+        rak_server.do_send(SetCapture(Some(capture.clone())));
+        Server {
+            capture,
+            path,
+            server_addr: addr,
+        }
+    })
+}
+
+#[test]
+fn capture_and_replay() {
+    System::run(||{
+        let server_addr: SocketAddr = "127.0.0.1:19145".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()));
+
+        let client1_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let client1 = block_on(create_client(114514, client1_addr));
+        client1.do_send(Connect(server_addr));
+    }).unwrap();
+}
@@ -0,0 +1,155 @@
+use std::{rc::Rc, time::Duration};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, ClientState, ConnectError, RakClient, RakClientEvent, RakClientNotice},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+
+const SERVER: &str = "10.0.0.1:19132";
+const NOBODY: &str = "10.0.0.9:19132";
+
+struct Client {
+    rak_client: Rc<ClientHandle>,
+    states: Vec<ClientState>,
+    expected: Vec<ClientState>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                let rak_client = self.rak_client.clone();
+                let check = async move {
+                    assert_eq!(rak_client.state().await, Some(ClientState::Connected));
+                    assert_eq!(
+                        rak_client.try_connect(SERVER.parse().unwrap()).await,
+                        Err(ConnectError::Busy(ClientState::Connected))
+                    );
+                    rak_client.disconnect();
+                };
+                ctx.spawn(check.into_actor(self));
+            }
+            RakClientEvent::ConnectionFailed(_) => panic!("cancelled connect must not fail"),
+            _ => {}
+        }
+    }
+}
+
+impl Handler<RakClientNotice> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientNotice, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientNotice::StateChanged(state) = msg {
+            self.states.push(state);
+        }
+        if self.states.len() == self.expected.len() {
+            assert_eq!(self.states, self.expected);
+            System::current().stop();
+        }
+    }
+}
+
+struct Server;
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+fn client(network: &MemoryNetwork, expected: Vec<ClientState>) -> Addr<Client> {
+    let network = network.clone();
+    Client::create(move |ctx| {
+        let rak_client = RakClient::init_in_memory(
+            &network,
+            "10.0.0.2:40000".parse().unwrap(),
+            114514,
+            ctx.address(),
+            System::current().arbiter(),
+        );
+        rak_client.set_notices(Some(ctx.address().recipient()));
+        Client {
+            rak_client: Rc::new(rak_client),
+            states: Vec::new(),
+            expected,
+        }
+    })
+}
+
+#[test]
+fn state_changes() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            RakServer::new_in_memory(
+                &server_network,
+                SERVER.parse().unwrap(),
+                0x1919,
+                "state".to_owned(),
+                ctx.address(),
+                1,
+            );
+            Server
+        });
+        let client = client(
+            &network,
+            vec![
+                ClientState::Discovering,
+                ClientState::Connecting,
+                ClientState::Connected,
+                ClientState::Closing,
+                ClientState::Idle,
+            ],
+        );
+        client.do_send(Start { cancel: false });
+    })
+    .unwrap();
+}
+
+#[test]
+fn cancel_connect() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let client = client(&network, vec![ClientState::Discovering, ClientState::Idle]);
+        client.do_send(Start { cancel: true });
+    })
+    .unwrap();
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Start {
+    cancel: bool,
+}
+
+impl Handler<Start> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: Start, ctx: &mut Self::Context) -> Self::Result {
+        let rak_client = self.rak_client.clone();
+        let start = async move {
+            assert_eq!(rak_client.state().await, Some(ClientState::Idle));
+            if !msg.cancel {
+                rak_client.connect(SERVER.parse().unwrap());
+                return;
+            }
+            rak_client
+                .try_connect(NOBODY.parse().unwrap())
+                .await
+                .unwrap();
+            actix::clock::delay_for(Duration::from_millis(100)).await;
+            assert_eq!(rak_client.state().await, Some(ClientState::Discovering));
+            rak_client.cancel_connect();
+        };
+        ctx.spawn(start.into_actor(self));
+    }
+}
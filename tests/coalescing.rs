@@ -0,0 +1,141 @@
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    capture::{Capture, PcapReader, SetCapture},
+    client::{RakClient, RakClientEvent},
+    inspect::{dissect, Dissection, Message},
+    packets::Reliability,
+    send::{Coalescing, SendOptions},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::Bytes;
+
+struct Client {
+    received: Arc<AtomicUsize>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(_) = msg {
+            self.received.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+struct Server {
+    capture: Capture,
+    path: PathBuf,
+    server_addr: SocketAddr,
+    received: Arc<AtomicUsize>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Server {
+    fn sent_frames(&self) -> Vec<Vec<Message>> {
+        self.capture.flush().unwrap();
+        PcapReader::open(&self.path)
+            .unwrap()
+            .datagrams()
+            .unwrap()
+            .iter()
+            .filter(|x| x.source == self.server_addr)
+            .filter_map(|x| match dissect(&x.payload) {
+                Dissection::FrameSet { frames, .. } => {
+                    Some(frames.into_iter().map(|x| x.message).collect())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            handle.set_coalescing(Coalescing::new(Duration::from_secs(60), usize::MAX));
+            let options = SendOptions::new().reliability(Reliability::Reliable);
+            handle.send_with(Bytes::from(vec![0xf0; 2000]), options.clone());
+            for id in 0xf1..=0xf3 {
+                handle.send_with(Bytes::copy_from_slice(&[id, 0, 0, 0]), options.clone());
+            }
+            ctx.run_later(Duration::from_millis(300), move |me, ctx| {
+                assert!(me
+                    .sent_frames()
+                    .iter()
+                    .flatten()
+                    .all(|x| !matches!(x, Message::User { .. } | Message::Fragment { .. })));
+                assert_eq!(me.received.load(Ordering::SeqCst), 0);
+                handle.flush();
+                ctx.run_later(Duration::from_millis(300), |me, _ctx| {
+                    assert!(me.sent_frames().iter().any(|x| {
+                        x.iter().any(|x| matches!(x, Message::Fragment { .. }))
+                            && x.iter().any(|x| matches!(x, Message::User { .. }))
+                    }));
+                    assert_eq!(me.received.load(Ordering::SeqCst), 4);
+                    System::current().stop();
+                });
+            });
+        }
+    }
+}
+
+#[test]
+fn coalescing() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:0".parse().unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let path = std::env::temp_dir().join("actix-raknet-coalescing.pcap");
+        let capture = Capture::create(&path).unwrap();
+        let server_network = network.clone();
+        let server_received = received.clone();
+        Server::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "coalescing".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetCapture(Some(capture.clone())));
+            Server {
+                capture,
+                path,
+                server_addr,
+                received: server_received,
+            }
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            Client { received }
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::client::{ClientHandle, RakClient, RakClientEvent};
+use bytes::BytesMut;
+
+pub struct Client {
+    rak_client: ClientHandle,
+    hook: Box<dyn FnMut(RakClientEvent)>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Connected = msg {
+            let packet: &[u8] = b"Hello Server!";
+            self.rak_client.packet(BytesMut::from(packet));
+        }
+        (self.hook)(msg);
+    }
+}
+
+impl Handler<Connect> for Client {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
+        self.rak_client.connect(msg.0);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Connect(pub SocketAddr);
+
+pub async fn create_client(guid: u64, addr: SocketAddr) -> Addr<Client> {
+    create_client_with(guid, addr, |_| {}).await
+}
+
+pub async fn create_client_with<F>(guid: u64, addr: SocketAddr, hook: F) -> Addr<Client>
+where
+    F: FnMut(RakClientEvent) + 'static,
+{
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Client::create(|ctx| {
+        let rak_client = RakClient::init(socket, guid, ctx.address(), System::current().arbiter());
+        Client {
+            rak_client,
+            hook: Box::new(hook),
+        }
+    })
+}
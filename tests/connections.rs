@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::server::{
+    ConnectionCount, ConnectionState, FindConnection, ListConnections, Peer, RakServer,
+    RakServerEvent,
+};
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client, Connect};
+
+struct Server {
+    rak_server: Addr<RakServer<Self>>,
+    greeted: usize,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Packet(handle, _) => {
+                self.greeted += 1;
+                if self.greeted != 2 {
+                    return;
+                }
+                let rak_server = self.rak_server.clone();
+                let address = handle.address;
+                async move {
+                    assert_eq!(rak_server.send(ConnectionCount).await.unwrap(), 2);
+
+                    let mut connections = rak_server.send(ListConnections).await.unwrap();
+                    connections.sort_by_key(|x| x.guid);
+                    assert_eq!(connections.len(), 2);
+                    assert_eq!(connections[0].guid, 1);
+                    assert_eq!(connections[1].guid, 2);
+                    assert!(connections
+                        .iter()
+                        .all(|x| x.state == ConnectionState::Connected
+                            && x.connected_since.is_some()));
+
+                    let by_address = rak_server
+                        .send(FindConnection(Peer::Address(address)))
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(by_address.guid, handle.guid);
+                    assert!(rak_server
+                        .send(FindConnection(Peer::Guid(99)))
+                        .await
+                        .unwrap()
+                        .is_none());
+
+                    let kicked = rak_server
+                        .send(FindConnection(Peer::Guid(1)))
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    kicked.disconnect();
+                }
+                .into_actor(self)
+                .wait(ctx);
+            }
+            RakServerEvent::Disconnected(_, guid) => {
+                assert_eq!(guid, 1);
+                System::current().stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn create_server(guid: u64, addr: SocketAddr, motd: String) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 2);
+        Server {
+            rak_server,
+            greeted: 0,
+        }
+    })
+}
+
+#[test]
+fn connections() {
+    System::run(||{
+        let server_addr: SocketAddr = "127.0.0.1:19147".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()));
+
+        for guid in 1..=2 {
+            let client_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            let client = block_on(create_client(guid, client_addr));
+            client.do_send(Connect(server_addr));
+        }
+    }).unwrap();
+}
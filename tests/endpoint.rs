@@ -0,0 +1,128 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, ConnectError, RakClientEvent},
+    endpoint::{ClientEndpoint, EndpointHandle},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::{Bytes, BytesMut};
+
+const SERVERS: u16 = 3;
+
+struct Upstream {
+    server: SocketAddr,
+    endpoint: EndpointHandle,
+    rak_client: Option<ClientHandle>,
+    done: Arc<AtomicUsize>,
+}
+
+impl Actor for Upstream {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let rak_client = self.endpoint.client(
+            self.server.port() as u64,
+            ctx.address(),
+            System::current().arbiter(),
+        );
+        rak_client.connect(self.server);
+        self.rak_client = Some(rak_client);
+    }
+}
+
+impl Handler<RakClientEvent> for Upstream {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                let payload = self.server.to_string();
+                let rak_client = self.rak_client.as_ref().unwrap();
+                rak_client.packet(BytesMut::from(payload.as_bytes()));
+
+                let duplicate =
+                    self.endpoint
+                        .client(0xdead, ctx.address(), System::current().arbiter());
+                let server = self.server;
+                let refused = async move {
+                    assert_eq!(
+                        duplicate.try_connect(server).await,
+                        Err(ConnectError::AddressInUse)
+                    );
+                };
+                ctx.spawn(refused.into_actor(self));
+            }
+            RakClientEvent::Packet(bytes) => {
+                assert_eq!(bytes, Bytes::from(self.server.to_string()));
+                if self.done.fetch_add(1, Ordering::SeqCst) + 1 == SERVERS as usize {
+                    assert_eq!(self.endpoint.connections(), SERVERS as usize);
+                    System::current().stop();
+                }
+            }
+            RakClientEvent::ConnectionFailed(_) | RakClientEvent::Disconnected => {
+                panic!("upstream {} failed", self.server)
+            }
+        }
+    }
+}
+
+struct Server;
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(handle, bytes) = msg {
+            handle.send(BytesMut::from(&bytes[..]));
+        }
+    }
+}
+
+#[test]
+fn many_upstreams_one_socket() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let servers: Vec<SocketAddr> = (0..SERVERS)
+            .map(|i| SocketAddr::from(([10, 0, 0, 1], 19132 + i)))
+            .collect();
+        for server in servers.iter().copied() {
+            let network = network.clone();
+            Server::create(move |ctx| {
+                RakServer::new_in_memory(
+                    &network,
+                    server,
+                    server.port() as u64,
+                    "endpoint".to_owned(),
+                    ctx.address(),
+                    1,
+                );
+                Server
+            });
+        }
+        let endpoint = ClientEndpoint::bind_in_memory(
+            &network,
+            "10.0.0.2:40000".parse().unwrap(),
+            System::current().arbiter(),
+        );
+        let done = Arc::new(AtomicUsize::new(0));
+        for server in servers {
+            Upstream {
+                server,
+                endpoint: endpoint.clone(),
+                rak_client: None,
+                done: done.clone(),
+            }
+            .start();
+        }
+    })
+    .unwrap();
+}
@@ -0,0 +1,136 @@
+use actix_raknet::{
+    inspect::{dissect, parse_hex, Dissection, Message},
+    packets::{
+        decode, encode, ConnectionRequest, Frame, FrameSet, OpenConnectionRequest2, Reliability,
+        UnconnectedPing,
+    },
+};
+use bytes::BytesMut;
+
+#[test]
+fn dissect_frame_set() {
+    let mut request = Frame::new(
+        Reliability::ReliableOrdered,
+        encode(ConnectionRequest::new(0x1919, 42, false)),
+    );
+    request.message_index = 7;
+    request.order_index = 3;
+    request.order_channel = 2;
+    let mut fragment = Frame::new(Reliability::Reliable, BytesMut::from(&b"Hello"[..]));
+    fragment.split = true;
+    fragment.split_count = 2;
+    fragment.split_index = 1;
+    fragment.split_id = 5;
+    let frame_set = FrameSet {
+        header: 0x84,
+        sequence_number: 12,
+        datas: vec![request, fragment],
+    };
+
+    let dissection = dissect(&frame_set.encode());
+    match &dissection {
+        Dissection::FrameSet {
+            sequence_number,
+            frames,
+            ..
+        } => {
+            assert_eq!(*sequence_number, 12);
+            assert_eq!(frames.len(), 2);
+            assert_eq!(frames[0].frame.order_channel, 2);
+            match &frames[0].message {
+                Message::ConnectionRequest(x) => assert_eq!(x.guid, 0x1919),
+                x => panic!("unexpected message {:?}", x),
+            }
+            assert!(matches!(frames[1].message, Message::Fragment { length: 5 }));
+        }
+        x => panic!("unexpected dissection {:?}", x),
+    }
+    let text = dissection.to_string();
+    assert!(text.contains("ReliableOrdered message_index=7 order_index=3 order_channel=2"));
+    assert!(text.contains("split=2/2 split_id=5"));
+    assert!(text.contains("ConnectionRequest guid=0x1919"));
+}
+
+#[test]
+fn dissect_ack_ranges() {
+    let datagram = parse_hex("c0 00 02 00 01 00 00 04 00 00 01 09 00 00").unwrap();
+    match dissect(&datagram) {
+        Dissection::Ack(ranges) => assert_eq!(ranges, vec![(1, 4), (9, 9)]),
+        x => panic!("unexpected dissection {:?}", x),
+    }
+    assert_eq!(dissect(&datagram).to_string(), "ACK #1..=#4, #9");
+}
+
+#[test]
+fn parse_hex_rejects_non_ascii() {
+    for text in ["é", "aé0", "c0 0é0"] {
+        let error = parse_hex(text).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+    assert!(parse_hex("0g").is_err());
+    assert_eq!(parse_hex("0xc0:01").unwrap(), vec![0xc0, 0x01]);
+}
+
+#[test]
+fn dissect_offline() {
+    let ping = encode(UnconnectedPing::new(1, 0x1919));
+    let dissection = dissect(&ping);
+    assert!(matches!(
+        dissection,
+        Dissection::Offline(Message::UnconnectedPing(_))
+    ));
+    assert_eq!(dissection.to_string(), "UnconnectedPing time=1 guid=0x1919");
+
+    let truncated = dissect(&ping[..5]);
+    assert!(matches!(
+        truncated,
+        Dissection::Offline(Message::Malformed { id: 0x01, .. })
+    ));
+}
+
+#[test]
+fn dissect_ack_receipt_reliability() {
+    let datagram = parse_hex("84 05 00 00 e0 00 08 01 00 00 02 00 00 00 fe").unwrap();
+    match dissect(&datagram) {
+        Dissection::FrameSet { frames, .. } => {
+            assert!(matches!(
+                frames[0].frame.reliability,
+                Reliability::ReliableOrderedWithAckReceipt
+            ));
+            assert_eq!(frames[0].frame.message_index, 1);
+            assert_eq!(frames[0].frame.order_index, 2);
+            assert!(matches!(
+                frames[0].message,
+                Message::User {
+                    id: 0xfe,
+                    length: 1
+                }
+            ));
+        }
+        x => panic!("unexpected dissection {:?}", x),
+    }
+}
+
+#[test]
+fn dissect_cookie_extension() {
+    let address = "10.0.0.1:19132".parse().unwrap();
+    let request2 = OpenConnectionRequest2::new(address, 1400, 0x1919);
+    let mut padded = encode(request2.clone());
+    padded.extend_from_slice(&[0; 8]);
+    assert_eq!(
+        decode::<OpenConnectionRequest2>(&padded).unwrap().cookie,
+        None
+    );
+    let extended = encode(request2.with_cookie(0xc0de));
+    assert_eq!(
+        decode::<OpenConnectionRequest2>(&extended).unwrap().cookie,
+        Some(0xc0de)
+    );
+    assert!(dissect(&extended)
+        .to_string()
+        .ends_with("guid=0x1919 cookie=0xc0de"));
+
+    let mut padded = encode(ConnectionRequest::new(0x1919, 42, false));
+    padded.extend_from_slice(&[0xff; 9]);
+    assert_eq!(decode::<ConnectionRequest>(&padded).unwrap().cookie, None);
+}
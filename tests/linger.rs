@@ -0,0 +1,81 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::RakClientEvent,
+    server::{RakServer, RakServerEvent, SetLinger},
+};
+use bytes::BytesMut;
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client_with, Client, Connect};
+
+struct Server {
+    received: Arc<AtomicBool>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Packet(handle, _) => {
+                handle.send(BytesMut::from(&[0x19; 20000][..]));
+                handle.disconnect();
+            }
+            RakServerEvent::Disconnected(_, _) => {
+                assert!(self.received.load(Ordering::SeqCst));
+                System::current().stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn create_client(guid: u64, addr: SocketAddr, received: Arc<AtomicBool>) -> Addr<Client> {
+    create_client_with(guid, addr, move |msg| {
+        if let RakClientEvent::Packet(bytes) = msg {
+            assert_eq!(bytes.len(), 20000);
+            received.store(true, Ordering::SeqCst);
+        }
+    })
+    .await
+}
+
+async fn create_server(
+    guid: u64,
+    addr: SocketAddr,
+    motd: String,
+    received: Arc<AtomicBool>,
+) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 1);
+        rak_server.do_send(SetLinger(Some(Duration::from_secs(3))));
+        Server { received }
+    })
+}
+
+#[test]
+fn linger() {
+    System::run(||{
+        let received = Arc::new(AtomicBool::new(false));
+        let server_addr: SocketAddr = "127.0.0.1:19149".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned(), received.clone()));
+
+        let client1_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let client1 = block_on(create_client(114514, client1_addr, received));
+        client1.do_send(Connect(server_addr));
+    }).unwrap();
+}
@@ -0,0 +1,98 @@
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::{
+    metrics::HandshakeFailure,
+    server::{GetMetrics, RakServer, RakServerEvent},
+};
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client, Connect};
+
+struct Server {
+    rak_server: Addr<RakServer<Self>>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, _) = msg {
+            let rak_server = self.rak_server.clone();
+            async move {
+                let metrics = rak_server.send(GetMetrics).await.unwrap();
+                assert_eq!(metrics.connected_sessions(), 1);
+                assert_eq!(metrics.handshakes_started(), 1);
+                assert_eq!(metrics.handshakes_failed(HandshakeFailure::Timeout), 0);
+                assert_eq!(metrics.worker_sessions().len(), 2);
+                assert_eq!(metrics.worker_sessions().iter().sum::<u64>(), 1);
+                assert!(metrics.datagrams_received() > 0);
+                assert!(metrics.bytes_sent() > 0);
+                #[cfg(feature = "metrics")]
+                {
+                    let text = metrics.openmetrics();
+                    assert!(text.contains("raknet_connected_sessions 1\n"));
+                    assert!(text.contains("raknet_worker_sessions{worker=\"1\"}"));
+                    assert!(text.ends_with("# EOF\n"));
+
+                    let http_addr: SocketAddr = "127.0.0.1:19156".parse().unwrap();
+                    actix::spawn(async move {
+                        let _ = actix_raknet::metrics::serve(metrics, http_addr).await;
+                    });
+                    let response = get(http_addr, "GET /metrics HTTP/1.1").await;
+                    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+                    assert!(response.ends_with("# EOF\n"));
+                    for request in ["GET / HTTP/1.1", "POST /metrics HTTP/1.1"] {
+                        let response = get(http_addr, request).await;
+                        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+                    }
+                }
+                System::current().stop();
+            }
+            .into_actor(self)
+            .wait(ctx);
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+async fn get(addr: SocketAddr, request: &str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // the exporter binds its listener after being spawned
+    let mut stream = loop {
+        match tokio::net::TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            Err(_) => actix::clock::delay_for(std::time::Duration::from_millis(10)).await,
+        }
+    };
+    let request = format!("{}\r\nHost: localhost\r\n\r\n", request);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+async fn create_server(guid: u64, addr: SocketAddr, motd: String) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 2);
+        Server { rak_server }
+    })
+}
+
+#[test]
+fn metrics() {
+    System::run(||{
+        let server_addr: SocketAddr = "127.0.0.1:19140".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()));
+
+        let client1_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let client1 = block_on(create_client(114514, client1_addr));
+        client1.do_send(Connect(server_addr));
+    }).unwrap();
+}
@@ -0,0 +1,114 @@
+use std::{net::SocketAddr, time::Duration};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, RakClient, RakClientEvent},
+    server::{RakServer, RakServerEvent, RakServerNotice, SetMigration, SetNotices},
+    transport::MemoryNetwork,
+};
+use bytes::BytesMut;
+
+const SERVER: &str = "10.0.0.1:19132";
+const BEFORE: &str = "10.0.0.2:40000";
+const AFTER: &str = "10.0.0.2:40001";
+
+struct Client {
+    network: MemoryNetwork,
+    rak_client: ClientHandle,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Connected = msg {
+            ctx.run_later(Duration::from_millis(100), |me, _ctx| {
+                assert!(me
+                    .network
+                    .rebind(BEFORE.parse().unwrap(), AFTER.parse().unwrap()));
+                me.rak_client
+                    .packet(BytesMut::from(&b"after rebinding"[..]));
+            });
+        }
+    }
+}
+
+struct Server {
+    migrated: bool,
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        let after: SocketAddr = AFTER.parse().unwrap();
+        match msg {
+            RakServerEvent::Packet(handle, bytes) => {
+                assert!(self.migrated);
+                assert_eq!(handle.address, after);
+                assert_eq!(&bytes[..], b"after rebinding");
+                System::current().stop();
+            }
+            RakServerEvent::Disconnected(_, _) => panic!("session was not migrated"),
+            _ => {}
+        }
+    }
+}
+
+impl Handler<RakServerNotice> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerNotice, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerNotice::Migrated(handle, from) => {
+                assert_eq!(from, BEFORE.parse().unwrap());
+                assert_eq!(handle.address, AFTER.parse().unwrap());
+                assert_eq!(handle.guid, 114514);
+                self.migrated = true;
+            }
+            RakServerNotice::Replaced(..) => panic!("session was replaced"),
+        }
+    }
+}
+
+#[test]
+fn nat_rebinding() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                SERVER.parse().unwrap(),
+                0x1919,
+                "migration".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetMigration(true));
+            rak_server.do_send(SetNotices(Some(ctx.address().recipient())));
+            Server { migrated: false }
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                BEFORE.parse().unwrap(),
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.set_migration(true);
+            rak_client.connect(SERVER.parse().unwrap());
+            Client {
+                network,
+                rak_client,
+            }
+        });
+    })
+    .unwrap();
+}
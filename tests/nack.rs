@@ -117,7 +117,7 @@ fn send_nack() {
                 if buff[0] & DATAGRAM_FLAG != 0 {
                     let frame_set = FrameSet::decode(&buff[..length]).unwrap();
                     for frame in frame_set.datas {
-                        if frame.data.len() == 0 {
+                        if frame.data.is_empty() {
                             continue;
                         }
                         if frame.data[0] == ConnectionRequest::ID {
@@ -0,0 +1,120 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, ConnectionFailedReason, RakClientEvent},
+    peer::RakPeer,
+    server::RakServerEvent,
+    transport::MemoryNetwork,
+};
+
+fn address(index: u64) -> SocketAddr {
+    SocketAddr::from(([10, 0, 0, index as u8], 19132))
+}
+
+struct Node {
+    peer: Option<RakPeer<Node>>,
+    dial: Vec<u64>,
+    clients: Vec<ClientHandle>,
+    events: Arc<AtomicUsize>,
+    expected: usize,
+}
+
+impl Node {
+    fn event(&self) {
+        if self.events.fetch_add(1, Ordering::SeqCst) + 1 == self.expected {
+            System::current().stop();
+        }
+    }
+}
+
+impl Actor for Node {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let peer = self.peer.as_ref().unwrap();
+        for index in self.dial.iter() {
+            let client = peer.connect(address(*index), ctx.address(), System::current().arbiter());
+            self.clients.push(client);
+        }
+    }
+}
+
+impl Handler<RakServerEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(_) = msg {
+            self.event();
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => self.event(),
+            RakClientEvent::ConnectionFailed(reason) => {
+                assert_eq!(reason, ConnectionFailedReason::AlreadyConnected);
+                self.event();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn node(
+    network: &MemoryNetwork,
+    index: u64,
+    dial: Vec<u64>,
+    events: &Arc<AtomicUsize>,
+    expected: usize,
+) {
+    let network = network.clone();
+    let events = events.clone();
+    Node::create(move |ctx| {
+        let peer = RakPeer::new_in_memory(
+            &network,
+            address(index),
+            index,
+            "peer".to_owned(),
+            ctx.address(),
+            1,
+        );
+        Node {
+            peer: Some(peer),
+            dial,
+            clients: Vec::new(),
+            events,
+            expected,
+        }
+    });
+}
+
+#[test]
+fn mesh() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let events = Arc::new(AtomicUsize::new(0));
+        node(&network, 1, vec![2, 3], &events, 6);
+        node(&network, 2, vec![3], &events, 6);
+        node(&network, 3, vec![], &events, 6);
+    })
+    .unwrap();
+}
+
+#[test]
+fn simultaneous_connect() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let events = Arc::new(AtomicUsize::new(0));
+        node(&network, 1, vec![2], &events, 3);
+        node(&network, 2, vec![1], &events, 3);
+    })
+    .unwrap();
+}
@@ -0,0 +1,189 @@
+use std::{net::SocketAddr, time::Duration};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, RakClient, RakClientEvent},
+    server::{Affinity, ListConnections, ListWorkers, RakServer, RakServerEvent, SetAffinity},
+    transport::MemoryNetwork,
+};
+use bytes::BytesMut;
+
+const SERVER: &str = "10.0.0.1:19132";
+
+struct Client {
+    rak_client: ClientHandle,
+    flood: bool,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let (RakClientEvent::Connected, true) = (msg, self.flood) {
+            ctx.run_interval(Duration::from_millis(2), |me, _ctx| {
+                me.rak_client.packet(BytesMut::from(&[0xfe; 16][..]));
+            });
+        }
+    }
+}
+
+fn client(network: &MemoryNetwork, guid: u64, flood: bool) {
+    let network = network.clone();
+    Client::create(move |ctx| {
+        let rak_client = RakClient::init_in_memory(
+            &network,
+            "10.0.0.2:0".parse().unwrap(),
+            guid,
+            ctx.address(),
+            System::current().arbiter(),
+        );
+        rak_client.connect(SERVER.parse().unwrap());
+        Client { rak_client, flood }
+    });
+}
+
+struct Server {
+    network: MemoryNetwork,
+    rak_server: Addr<RakServer<Server>>,
+    bot: Option<SocketAddr>,
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Server {
+    fn create(network: &MemoryNetwork, affinity: Option<Affinity>) -> Addr<Self> {
+        let network = network.clone();
+        Actor::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &network,
+                SERVER.parse().unwrap(),
+                0x1919,
+                "placement".to_owned(),
+                ctx.address(),
+                2,
+            );
+            rak_server.do_send(SetAffinity(affinity));
+            Server {
+                network,
+                rak_server,
+                bot: None,
+            }
+        })
+    }
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        let handle = match msg {
+            RakServerEvent::Connected(handle) => handle,
+            _ => return,
+        };
+        match handle.guid {
+            1 => client(&self.network, 2, true),
+            2 => {
+                self.bot = Some(handle.address);
+                ctx.run_later(Duration::from_millis(1500), |me, _ctx| {
+                    client(&me.network, 3, false)
+                });
+            }
+            3 => client(&self.network, 4, false),
+            4 => {
+                let bot = self.bot.unwrap();
+                let workers = self.rak_server.send(ListWorkers);
+                ctx.spawn(async move { workers.await.unwrap() }.into_actor(self).map(
+                    move |workers, _me, _ctx| {
+                        let busy = workers.iter().find(|x| x.peers.contains(&bot)).unwrap();
+                        let idle = workers.iter().find(|x| x.index != busy.index).unwrap();
+                        assert!(busy.load > 0.0, "{:?}", workers);
+                        assert_eq!(busy.sessions, 1, "{:?}", workers);
+                        assert_eq!(idle.sessions, 3, "{:?}", workers);
+                        System::current().stop();
+                    },
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn load_aware_placement() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        Server::create(&network, None);
+        client(&network, 1, false);
+    })
+    .unwrap();
+}
+
+struct Grouped {
+    rak_server: Addr<RakServer<Grouped>>,
+    connected: usize,
+}
+
+impl Actor for Grouped {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Grouped {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(_) = msg {
+            self.connected += 1;
+            if self.connected < 4 {
+                return;
+            }
+            let conns = self.rak_server.send(ListConnections);
+            ctx.spawn(async move { conns.await.unwrap() }.into_actor(self).map(
+                |conns, _me, _ctx| {
+                    assert_eq!(conns.len(), 4);
+                    for conn in conns.iter() {
+                        let peer = conns
+                            .iter()
+                            .find(|x| x.guid != conn.guid && x.guid % 2 == conn.guid % 2);
+                        assert_eq!(peer.unwrap().worker, conn.worker, "{:?}", conns);
+                    }
+                    let even = conns.iter().find(|x| x.guid % 2 == 0).unwrap();
+                    let odd = conns.iter().find(|x| x.guid % 2 == 1).unwrap();
+                    assert_ne!(even.worker, odd.worker, "{:?}", conns);
+                    System::current().stop();
+                },
+            ));
+        }
+    }
+}
+
+#[test]
+fn affinity_groups() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_network = network.clone();
+        Grouped::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                SERVER.parse().unwrap(),
+                0x1919,
+                "placement".to_owned(),
+                ctx.address(),
+                2,
+            );
+            rak_server.do_send(SetAffinity(Some(Affinity::new(|_address, guid| {
+                Some(guid % 2)
+            }))));
+            Grouped {
+                rak_server,
+                connected: 0,
+            }
+        });
+        for guid in 0..4 {
+            client(&network, guid, false);
+        }
+    })
+    .unwrap();
+}
@@ -0,0 +1,102 @@
+use std::net::SocketAddr;
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{RakClient, RakClientEvent},
+    packets::Reliability,
+    send::{Priority, SendOptions},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::Bytes;
+
+const LOW: u8 = 0xf0;
+const MEDIUM: u8 = 0xf1;
+const HIGH: u8 = 0xf2;
+const IMMEDIATE: u8 = 0xf3;
+
+struct Client {
+    received: Vec<u8>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Packet(bytes) = msg {
+            self.received.push(bytes[0]);
+            if self.received.len() == 18 {
+                assert_eq!(self.received[0], IMMEDIATE);
+                assert_eq!(&self.received[1..5], &[HIGH; 4]);
+                assert_eq!(self.received[5], MEDIUM);
+                assert_eq!(self.received[6], LOW);
+                assert_eq!(&self.received[7..11], &[HIGH; 4]);
+                System::current().stop();
+            }
+        }
+    }
+}
+
+struct Server;
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            let options = SendOptions::new().reliability(Reliability::Reliable);
+            let send = |id: u8, priority: Priority| {
+                handle.send_with(
+                    Bytes::copy_from_slice(&[id, 0, 0, 0]),
+                    options.clone().priority(priority),
+                )
+            };
+            send(MEDIUM, Priority::Medium);
+            for _ in 0..8 {
+                send(LOW, Priority::Low);
+            }
+            for _ in 0..8 {
+                send(HIGH, Priority::High);
+            }
+            send(IMMEDIATE, Priority::Immediate);
+        }
+    }
+}
+
+#[test]
+fn priority() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:0".parse().unwrap();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "priority".to_owned(),
+                ctx.address(),
+                1,
+            );
+            Server
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            Client { received: vec![] }
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,264 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, RakClientEvent},
+    packets::{encode, NatPunch},
+    peer::RakPeer,
+    punch::{NatFacilitator, PunchEvent, PunchFailure, PunchHandle},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::BytesMut;
+
+const FACILITATOR: &str = "10.0.0.1:19132";
+
+fn private(index: u64) -> SocketAddr {
+    SocketAddr::from(([192, 168, index as u8, 2], 19132))
+}
+
+fn public(index: u64) -> SocketAddr {
+    SocketAddr::from(([203, 0, 113, index as u8], 0))
+}
+
+struct Node {
+    network: MemoryNetwork,
+    peer: Option<RakPeer<Node>>,
+    punch: Option<PunchHandle>,
+    target: Option<u64>,
+    client: Option<ClientHandle>,
+    events: Arc<AtomicUsize>,
+}
+
+impl Node {
+    fn event(&self) {
+        if self.events.fetch_add(1, Ordering::SeqCst) + 1 == 6 {
+            System::current().stop();
+        }
+    }
+}
+
+impl Actor for Node {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let peer = self.peer.as_ref().unwrap();
+        let punch = peer.punchthrough(FACILITATOR.parse().unwrap(), ctx.address().recipient());
+        if let Some(target) = self.target {
+            punch.punch(target);
+        }
+        self.punch = Some(punch);
+    }
+}
+
+impl Handler<PunchEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: PunchEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            PunchEvent::Ready {
+                guid,
+                address,
+                initiator,
+            } => {
+                let expected = self.network.public_address(private(guid));
+                assert_eq!(expected.unwrap_or(private(guid)), address);
+                assert_eq!(initiator, self.target.is_some());
+                if initiator {
+                    assert_eq!(
+                        self.network.inject(
+                            "10.0.0.9:19132".parse().unwrap(),
+                            address,
+                            &b"unsolicited"[..]
+                        ),
+                        expected.is_none()
+                    );
+                    let peer = self.peer.as_ref().unwrap();
+                    let client = peer.connect(address, ctx.address(), System::current().arbiter());
+                    self.client = Some(client);
+                }
+                self.event();
+            }
+            PunchEvent::Failed(guid, PunchFailure::UnknownPeer) => {
+                ctx.run_later(Duration::from_millis(50), move |act, _ctx| {
+                    act.punch.as_ref().unwrap().punch(guid);
+                });
+            }
+            PunchEvent::Failed(guid, reason) => panic!("punch to {} failed: {:?}", guid, reason),
+        }
+    }
+}
+
+impl Handler<RakServerEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Connected(handle) => {
+                assert_eq!(handle.guid, 1);
+                assert_eq!(
+                    self.network.public_address(private(1)),
+                    Some(handle.address)
+                );
+                self.event();
+            }
+            RakServerEvent::Packet(handle, bytes) => {
+                assert_eq!(&bytes[..], b"ping");
+                handle.send(BytesMut::from(&b"pong"[..]));
+                self.event();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                let client = self.client.as_ref().unwrap();
+                client.packet(BytesMut::from(&b"ping"[..]));
+                self.event();
+            }
+            RakClientEvent::Packet(bytes) => {
+                assert_eq!(&bytes[..], b"pong");
+                self.event();
+            }
+            RakClientEvent::ConnectionFailed(_) => panic!("direct connect failed"),
+            _ => {}
+        }
+    }
+}
+
+fn facilitator(network: &MemoryNetwork) {
+    let network = network.clone();
+    NatFacilitator::create(move |ctx| {
+        RakServer::new_in_memory(
+            &network,
+            FACILITATOR.parse().unwrap(),
+            0xfac,
+            "facilitator".to_owned(),
+            ctx.address(),
+            1,
+        );
+        NatFacilitator::new()
+    });
+}
+
+fn node(network: &MemoryNetwork, guid: u64, target: Option<u64>, events: &Arc<AtomicUsize>) {
+    network.add_nat(private(guid).ip(), public(guid).ip());
+    open_node(network, guid, target, events);
+}
+
+fn open_node(network: &MemoryNetwork, guid: u64, target: Option<u64>, events: &Arc<AtomicUsize>) {
+    let network = network.clone();
+    let events = events.clone();
+    Node::create(move |ctx| {
+        let peer = RakPeer::new_in_memory(
+            &network,
+            private(guid),
+            guid,
+            "peer".to_owned(),
+            ctx.address(),
+            1,
+        );
+        Node {
+            network,
+            peer: Some(peer),
+            punch: None,
+            target,
+            client: None,
+            events,
+        }
+    });
+}
+
+#[test]
+fn punch_through_nat() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let events = Arc::new(AtomicUsize::new(0));
+        facilitator(&network);
+        node(&network, 2, None, &events);
+        node(&network, 1, Some(2), &events);
+    })
+    .unwrap();
+}
+
+#[test]
+fn forged_punch() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let events = Arc::new(AtomicUsize::new(0));
+        facilitator(&network);
+        open_node(&network, 2, None, &events);
+        node(&network, 1, Some(2), &events);
+        let forged = encode(NatPunch::new(1, 0));
+        actix::spawn(async move {
+            loop {
+                network.inject(
+                    "10.0.0.9:19132".parse().unwrap(),
+                    private(2),
+                    forged.clone(),
+                );
+                actix::clock::delay_for(Duration::from_millis(5)).await;
+            }
+        });
+    })
+    .unwrap();
+}
+
+struct Lonely {
+    _peer: RakPeer<Lonely>,
+    _punch: PunchHandle,
+}
+
+impl Actor for Lonely {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Lonely {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+impl Handler<PunchEvent> for Lonely {
+    type Result = ();
+    fn handle(&mut self, msg: PunchEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            PunchEvent::Failed(7, PunchFailure::UnknownPeer) => System::current().stop(),
+            _ => panic!("unexpected punch event"),
+        }
+    }
+}
+
+#[test]
+fn unknown_peer() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        facilitator(&network);
+        network.add_nat(private(1).ip(), public(1).ip());
+        Lonely::create(move |ctx| {
+            let peer = RakPeer::new_in_memory(
+                &network,
+                private(1),
+                1,
+                "peer".to_owned(),
+                ctx.address(),
+                1,
+            );
+            let punch = peer.punchthrough(FACILITATOR.parse().unwrap(), ctx.address().recipient());
+            punch.punch(7);
+            Lonely {
+                _peer: peer,
+                _punch: punch,
+            }
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,174 @@
+use std::{net::SocketAddr, time::Duration};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{RakClient, RakClientEvent},
+    packets::{encode, Ack, Reliability},
+    send::{Delivery, RateLimit, SendOptions},
+    server::{RakServer, RakServerEvent, SetBandwidth},
+    transport::MemoryNetwork,
+};
+use bytes::Bytes;
+
+struct Client;
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, _msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+struct Server {
+    network: MemoryNetwork,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            let network = self.network.clone();
+            async move {
+                let acked = handle.send_with_receipt(
+                    Bytes::from_static(b"inventory"),
+                    SendOptions::new().reliability(Reliability::ReliableOrderedWithAckReceipt),
+                );
+                assert_eq!(acked.await, Delivery::Acked);
+
+                network.unbind(handle.address);
+                let unreliable = handle.send_with_receipt(
+                    Bytes::from_static(b"position"),
+                    SendOptions::new().reliability(Reliability::UnreliableWithAckReceipt),
+                );
+                assert_eq!(unreliable.await, Delivery::Lost);
+
+                let reliable = handle.send_with_receipt(
+                    Bytes::from_static(b"inventory"),
+                    SendOptions::new().reliability(Reliability::ReliableWithAckReceipt),
+                );
+                handle.disconnect_now();
+                assert_eq!(reliable.await, Delivery::Lost);
+                System::current().stop();
+            }
+            .into_actor(self)
+            .spawn(ctx);
+        }
+    }
+}
+
+#[test]
+fn receipt() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:0".parse().unwrap();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "receipt".to_owned(),
+                ctx.address(),
+                1,
+            );
+            Server {
+                network: server_network,
+            }
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            Client
+        });
+    })
+    .unwrap();
+}
+
+struct SplitServer {
+    network: MemoryNetwork,
+    server_addr: SocketAddr,
+}
+impl Actor for SplitServer {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for SplitServer {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Connected(handle) = msg {
+            let network = self.network.clone();
+            let server_addr = self.server_addr;
+            async move {
+                network.unbind(handle.address);
+                let mut receipt = handle.send_with_receipt(
+                    Bytes::from(vec![0xfe; 6000]),
+                    SendOptions::new().reliability(Reliability::ReliableWithAckReceipt),
+                );
+                // the pacer has released only the first fragments, acknowledge
+                // everything that went out so far
+                actix::clock::delay_for(Duration::from_millis(50)).await;
+                network.inject(handle.address, server_addr, encode(Ack::new((0, 1000))));
+                let pending = tokio::time::timeout(Duration::from_millis(300), &mut receipt).await;
+                assert!(pending.is_err(), "{:?}", pending);
+
+                handle.disconnect_now();
+                assert_eq!(receipt.await, Delivery::Lost);
+                System::current().stop();
+            }
+            .into_actor(self)
+            .spawn(ctx);
+        }
+    }
+}
+
+#[test]
+fn partly_acked_split() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "10.0.0.1:19132".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:0".parse().unwrap();
+        let server_network = network.clone();
+        SplitServer::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                server_addr,
+                0x1919,
+                "receipt".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetBandwidth {
+                per_connection: Some(RateLimit::new(2_000, 1_500)),
+                global: None,
+            });
+            SplitServer {
+                network: server_network,
+                server_addr,
+            }
+        });
+        Client::create(move |ctx| {
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                client_addr,
+                114514,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(server_addr);
+            Client
+        });
+    })
+    .unwrap();
+}
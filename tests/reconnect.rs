@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{
+        ClientHandle, ConnectionFailedReason, RakClient, RakClientEvent, RakClientNotice,
+        ReconnectPolicy, RetryReason,
+    },
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::BytesMut;
+
+const SERVER: &str = "10.0.0.1:19132";
+
+struct Client {
+    rak_client: ClientHandle,
+    events: Vec<String>,
+    expected: Vec<&'static str>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Client {
+    fn record(&mut self, event: String) {
+        self.events.push(event);
+        if self.events.len() == self.expected.len() {
+            assert_eq!(self.events, self.expected);
+            System::current().stop();
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        let event = match msg {
+            RakClientEvent::ConnectionFailed(reason) => {
+                assert_eq!(reason, ConnectionFailedReason::AlreadyConnected);
+                "failed"
+            }
+            RakClientEvent::Connected => {
+                self.rak_client.packet(BytesMut::from(&b"kick me"[..]));
+                "connected"
+            }
+            RakClientEvent::Packet(_) => return,
+            RakClientEvent::Disconnected => "disconnected",
+        };
+        self.record(event.to_owned());
+    }
+}
+
+impl Handler<RakClientNotice> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientNotice, _ctx: &mut Self::Context) -> Self::Result {
+        let event = match msg {
+            RakClientNotice::Reconnecting(attempt) => format!("reconnecting {}", attempt),
+            RakClientNotice::Reconnected => "reconnected".to_owned(),
+            RakClientNotice::StateChanged(_) => return,
+        };
+        self.record(event);
+    }
+}
+
+struct Server {
+    kicked: bool,
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(handle, _) = msg {
+            if !self.kicked {
+                self.kicked = true;
+                handle.disconnect();
+            }
+        }
+    }
+}
+
+fn client(
+    network: &MemoryNetwork,
+    local: &str,
+    policy: Option<ReconnectPolicy>,
+    expected: Vec<&'static str>,
+) {
+    let network = network.clone();
+    let local = local.parse().unwrap();
+    Client::create(move |ctx| {
+        let rak_client = RakClient::init_in_memory(
+            &network,
+            local,
+            114514,
+            ctx.address(),
+            System::current().arbiter(),
+        );
+        rak_client.set_reconnect(policy);
+        rak_client.set_notices(Some(ctx.address().recipient()));
+        rak_client.connect(SERVER.parse().unwrap());
+        Client {
+            rak_client,
+            events: Vec::new(),
+            expected,
+        }
+    });
+}
+
+fn server(network: &MemoryNetwork, kick: bool) {
+    let network = network.clone();
+    Server::create(move |ctx| {
+        RakServer::new_in_memory(
+            &network,
+            SERVER.parse().unwrap(),
+            0x1919,
+            "reconnect".to_owned(),
+            ctx.address(),
+            1,
+        );
+        Server { kicked: !kick }
+    });
+}
+
+#[test]
+fn reconnect_after_disconnect() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        server(&network, true);
+        let policy =
+            ReconnectPolicy::new(Some(3), Duration::from_millis(50), Duration::from_secs(1));
+        client(
+            &network,
+            "10.0.0.2:40000",
+            Some(policy),
+            vec![
+                "connected",
+                "disconnected",
+                "reconnecting 1",
+                "reconnected",
+                "connected",
+            ],
+        );
+    })
+    .unwrap();
+}
+
+#[test]
+fn give_up_after_max_attempts() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        server(&network, false);
+        client(
+            &network,
+            "10.0.0.2:40000",
+            None,
+            vec!["connected", "disconnected"],
+        );
+        let policy = ReconnectPolicy::new(
+            Some(2),
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        )
+        .retry_on(vec![RetryReason::AlreadyConnected]);
+        let network = network.clone();
+        Arbiter::spawn(async move {
+            actix::clock::delay_for(Duration::from_millis(200)).await;
+            client(
+                &network,
+                "10.0.0.3:40000",
+                Some(policy),
+                vec!["reconnecting 1", "reconnecting 2", "failed"],
+            );
+        });
+    })
+    .unwrap();
+}
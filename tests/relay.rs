@@ -0,0 +1,500 @@
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    capture::{Capture, PcapReader, SetCapture},
+    client::{ClientHandle, RakClientEvent},
+    packets::{FrameSet, Packet, RelayData, Reliability},
+    peer::RakPeer,
+    punch::{NatFacilitator, PunchEvent, PunchHandle},
+    relay::{relay_address, ListRelays, RelayEvent, RelayGrant, RelayHandle, RelayServer},
+    send::{RateLimit, SendOptions},
+    server::{RakServer, RakServerEvent},
+    transport::MemoryNetwork,
+};
+use bytes::{Bytes, BytesMut};
+
+const FACILITATOR: &str = "10.0.0.1:19132";
+const RELAY: &str = "10.0.0.3:19132";
+const TOKEN: u64 = 0x7e57;
+
+fn private(index: u64) -> SocketAddr {
+    SocketAddr::from(([192, 168, index as u8, 2], 19132))
+}
+
+fn public(index: u64) -> SocketAddr {
+    SocketAddr::from(([203, 0, 113, index as u8], 0))
+}
+
+fn relay_server(network: &MemoryNetwork, relay: RelayServer) -> Addr<RelayServer> {
+    let network = network.clone();
+    RelayServer::create(move |ctx| {
+        RakServer::new_in_memory(
+            &network,
+            RELAY.parse().unwrap(),
+            0xde1a,
+            "relay".to_owned(),
+            ctx.address(),
+            1,
+        );
+        relay
+    })
+}
+
+fn peer<T>(network: &MemoryNetwork, address: SocketAddr, guid: u64, ctx: &Context<T>) -> RakPeer<T>
+where
+    T: Actor<Context = Context<T>> + Handler<RakServerEvent>,
+{
+    RakPeer::new_in_memory(network, address, guid, "peer".to_owned(), ctx.address(), 1)
+}
+
+struct Node {
+    peer: Option<RakPeer<Node>>,
+    punch: Option<PunchHandle>,
+    target: Option<u64>,
+    client: Option<ClientHandle>,
+    events: Arc<AtomicUsize>,
+}
+
+impl Node {
+    fn event(&self) {
+        if self.events.fetch_add(1, Ordering::SeqCst) + 1 == 5 {
+            System::current().stop();
+        }
+    }
+}
+
+impl Actor for Node {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let peer = self.peer.as_ref().unwrap();
+        let punch = peer.punchthrough(FACILITATOR.parse().unwrap(), ctx.address().recipient());
+        punch.fallback(Some(RELAY.parse().unwrap()));
+        if let Some(target) = self.target {
+            ctx.run_later(Duration::from_millis(200), move |act, _ctx| {
+                act.punch.as_ref().unwrap().punch(target);
+            });
+        }
+        self.punch = Some(punch);
+    }
+}
+
+impl Handler<PunchEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: PunchEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            PunchEvent::Ready {
+                guid,
+                address,
+                initiator,
+            } => {
+                assert_eq!(address, relay_address(guid));
+                if initiator {
+                    let peer = self.peer.as_ref().unwrap();
+                    let client = peer.connect(address, ctx.address(), System::current().arbiter());
+                    self.client = Some(client);
+                }
+                self.event();
+            }
+            PunchEvent::Failed(guid, reason) => panic!("punch to {} failed: {:?}", guid, reason),
+        }
+    }
+}
+
+impl Handler<RakServerEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Connected(handle) => {
+                assert_eq!(handle.address, relay_address(1));
+                self.event();
+            }
+            RakServerEvent::Packet(handle, bytes) => {
+                assert_eq!(handle.guid, 1);
+                assert_eq!(bytes, Bytes::from_static(b"hello"));
+                self.event();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Node {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                let client = self.client.as_ref().unwrap();
+                client.packet(BytesMut::from(&b"hello"[..]));
+                self.event();
+            }
+            RakClientEvent::ConnectionFailed(_) => panic!("relayed connect failed"),
+            _ => {}
+        }
+    }
+}
+
+fn node(network: &MemoryNetwork, guid: u64, target: Option<u64>, events: &Arc<AtomicUsize>) {
+    network.add_symmetric_nat(private(guid).ip(), public(guid).ip());
+    let network = network.clone();
+    let events = events.clone();
+    Node::create(move |ctx| Node {
+        peer: Some(peer(&network, private(guid), guid, ctx)),
+        punch: None,
+        target,
+        client: None,
+        events,
+    });
+}
+
+#[test]
+fn fallback_behind_symmetric_nat() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let events = Arc::new(AtomicUsize::new(0));
+        let facilitator_network = network.clone();
+        let relay = relay_server(&network, RelayServer::default());
+        NatFacilitator::create(move |ctx| {
+            RakServer::new_in_memory(
+                &facilitator_network,
+                FACILITATOR.parse().unwrap(),
+                0xfac,
+                "facilitator".to_owned(),
+                ctx.address(),
+                1,
+            );
+            NatFacilitator::new().with_relay(relay.recipient())
+        });
+        node(&network, 2, None, &events);
+        node(&network, 1, Some(2), &events);
+    })
+    .unwrap();
+}
+
+struct Idle {
+    _peer: RakPeer<Idle>,
+    _relay: RelayHandle,
+    ready: Arc<AtomicUsize>,
+    closed: Arc<AtomicUsize>,
+}
+
+impl Actor for Idle {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Idle {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+impl Handler<RelayEvent> for Idle {
+    type Result = ();
+    fn handle(&mut self, msg: RelayEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RelayEvent::Ready { .. } => {
+                self.ready.fetch_add(1, Ordering::SeqCst);
+            }
+            RelayEvent::Closed(_) => {
+                assert_eq!(self.ready.load(Ordering::SeqCst), 2);
+                if self.closed.fetch_add(1, Ordering::SeqCst) + 1 == 2 {
+                    System::current().stop();
+                }
+            }
+        }
+    }
+}
+
+fn expire(idle: Duration) {
+    System::run(move || {
+        let network = MemoryNetwork::new();
+        let server = relay_server(&network, RelayServer::new(None, idle));
+        server.do_send(RelayGrant {
+            peers: (1, 2),
+            token: TOKEN,
+        });
+        let ready = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(AtomicUsize::new(0));
+        for (guid, target) in [(1, 2), (2, 1)] {
+            let network = network.clone();
+            let ready = ready.clone();
+            let closed = closed.clone();
+            Idle::create(move |ctx| {
+                let peer = peer(&network, public(guid), guid, ctx);
+                let relay = peer.relay(RELAY.parse().unwrap(), ctx.address().recipient());
+                relay.open(target, TOKEN);
+                Idle {
+                    _peer: peer,
+                    _relay: relay,
+                    ready,
+                    closed,
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn idle_expiry() {
+    expire(Duration::from_millis(400));
+}
+
+#[test]
+fn zero_idle() {
+    expire(Duration::ZERO);
+}
+
+struct Flood {
+    peer: RakPeer<Flood>,
+    _relay: RelayHandle,
+    server: Addr<RelayServer>,
+    target: Option<u64>,
+    client: Option<ClientHandle>,
+}
+
+impl Actor for Flood {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Flood {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+impl Handler<RelayEvent> for Flood {
+    type Result = ();
+    fn handle(&mut self, msg: RelayEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RelayEvent::Ready { address, .. } => {
+                if self.target.is_some() {
+                    let client =
+                        self.peer
+                            .connect(address, ctx.address(), System::current().arbiter());
+                    self.client = Some(client);
+                }
+            }
+            RelayEvent::Closed(guid) => panic!("relay to {} closed", guid),
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Flood {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Connected = msg {
+            let client = self.client.as_ref().unwrap();
+            let options = SendOptions::new().reliability(Reliability::Unreliable);
+            for _ in 0..40 {
+                client.packet_with(Bytes::from(vec![0xfe; 500]), options.clone());
+            }
+            let server = self.server.clone();
+            let check = async move {
+                actix::clock::delay_for(Duration::from_millis(300)).await;
+                let relays = server.send(ListRelays).await.unwrap();
+                assert_eq!(relays.len(), 1);
+                assert_eq!(relays[0].peers, (1, 2));
+                assert!(relays[0].dropped > 0);
+                assert!(relays[0].forwarded < 40 * 500);
+                System::current().stop();
+            };
+            ctx.spawn(check.into_actor(self));
+        }
+    }
+}
+
+#[test]
+fn bandwidth_limit() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server = relay_server(
+            &network,
+            RelayServer::new(Some(RateLimit::new(4000, 4000)), Duration::from_secs(30)),
+        );
+        server.do_send(RelayGrant {
+            peers: (1, 2),
+            token: TOKEN,
+        });
+        for (guid, target) in [(1, Some(2)), (2, None)] {
+            let network = network.clone();
+            let server = server.clone();
+            Flood::create(move |ctx| {
+                let peer = peer(&network, public(guid), guid, ctx);
+                let relay = peer.relay(RELAY.parse().unwrap(), ctx.address().recipient());
+                relay.open(3 - guid, TOKEN);
+                Flood {
+                    peer,
+                    _relay: relay,
+                    server,
+                    target,
+                    client: None,
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+struct Impostor {
+    _peer: RakPeer<Impostor>,
+    _relay: RelayHandle,
+}
+
+impl Actor for Impostor {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Impostor {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+impl Handler<RelayEvent> for Impostor {
+    type Result = ();
+    fn handle(&mut self, msg: RelayEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RelayEvent::Closed(2) => System::current().stop(),
+            _ => panic!("relay opened without a grant"),
+        }
+    }
+}
+
+#[test]
+fn ungranted_request() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let server = relay_server(&network, RelayServer::default());
+        server.do_send(RelayGrant {
+            peers: (1, 2),
+            token: TOKEN,
+        });
+        Impostor::create(move |ctx| {
+            let peer = peer(&network, public(1), 1, ctx);
+            let relay = peer.relay(RELAY.parse().unwrap(), ctx.address().recipient());
+            relay.open(2, TOKEN + 1);
+            Impostor {
+                _peer: peer,
+                _relay: relay,
+            }
+        });
+    })
+    .unwrap();
+}
+
+struct Bulk {
+    peer: RakPeer<Bulk>,
+    _relay: RelayHandle,
+    server: Addr<RakServer<RelayServer>>,
+    capture: Capture,
+    path: PathBuf,
+    target: Option<u64>,
+    client: Option<ClientHandle>,
+}
+
+impl Actor for Bulk {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Bulk {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, bytes) = msg {
+            assert_eq!(bytes.len(), 3000);
+            self.capture.flush().unwrap();
+            let datagrams = PcapReader::open(&self.path).unwrap().datagrams().unwrap();
+            let frames: Vec<_> = datagrams
+                .iter()
+                .filter(|x| x.payload[0] & 0x80 != 0)
+                .flat_map(|x| FrameSet::decode(&x.payload).unwrap().datas)
+                .filter(|x| x.data.first() == Some(&RelayData::ID))
+                .collect();
+            assert!(frames.len() > 6);
+            assert!(frames
+                .iter()
+                .all(|x| !x.split && matches!(x.reliability, Reliability::Unreliable)));
+            System::current().stop();
+        }
+    }
+}
+
+impl Handler<RelayEvent> for Bulk {
+    type Result = ();
+    fn handle(&mut self, msg: RelayEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RelayEvent::Ready { address, .. } => {
+                if self.target.is_some() {
+                    let client =
+                        self.peer
+                            .connect(address, ctx.address(), System::current().arbiter());
+                    self.client = Some(client);
+                }
+            }
+            RelayEvent::Closed(guid) => panic!("relay to {} closed", guid),
+        }
+    }
+}
+
+impl Handler<RakClientEvent> for Bulk {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakClientEvent::Connected = msg {
+            self.server.do_send(SetCapture(Some(self.capture.clone())));
+            let client = self.client.as_ref().unwrap();
+            client.packet(BytesMut::from(&[0xfe; 3000][..]));
+        }
+    }
+}
+
+#[test]
+fn relayed_mtu() {
+    System::run(|| {
+        let network = MemoryNetwork::new();
+        let path = std::env::temp_dir().join("actix-raknet-relay.pcap");
+        let capture = Capture::create(&path).unwrap();
+        let mut server = None;
+        let relay = RelayServer::create(|ctx| {
+            server = Some(RakServer::new_in_memory(
+                &network,
+                RELAY.parse().unwrap(),
+                0xde1a,
+                "relay".to_owned(),
+                ctx.address(),
+                1,
+            ));
+            RelayServer::default()
+        });
+        relay.do_send(RelayGrant {
+            peers: (1, 2),
+            token: TOKEN,
+        });
+        let server = server.unwrap();
+        for (guid, target) in [(1, Some(2)), (2, None)] {
+            let network = network.clone();
+            let server = server.clone();
+            let capture = capture.clone();
+            let path = path.clone();
+            Bulk::create(move |ctx| {
+                let peer = peer(&network, public(guid), guid, ctx);
+                let relay = peer.relay(RELAY.parse().unwrap(), ctx.address().recipient());
+                relay.open(3 - guid, TOKEN);
+                Bulk {
+                    peer,
+                    _relay: relay,
+                    server,
+                    capture,
+                    path,
+                    target,
+                    client: None,
+                }
+            });
+        }
+    })
+    .unwrap();
+}
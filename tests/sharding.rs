@@ -0,0 +1,113 @@
+use std::{
+    collections::HashSet,
+    net::{SocketAddr, UdpSocket},
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, RakClient, RakClientEvent},
+    listener::Listener,
+    server::{RakServer, RakServerEvent},
+};
+use bytes::BytesMut;
+
+struct Client {
+    rak_client: ClientHandle,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected => {
+                self.rak_client.packet(BytesMut::from(&[0xfe; 2000][..]));
+            }
+            RakClientEvent::Packet(_) => self.rak_client.disconnect(),
+            _ => {}
+        }
+    }
+}
+
+struct Server {
+    clients: usize,
+    peers: HashSet<SocketAddr>,
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Packet(handle, bytes) => {
+                assert_eq!(bytes.len(), 2000);
+                handle.send(BytesMut::from(&b"echo"[..]));
+            }
+            RakServerEvent::Disconnected(addr, _) => {
+                self.peers.insert(addr);
+                if self.peers.len() == self.clients {
+                    System::current().stop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn run(listener: Listener, clients: Vec<(SocketAddr, SocketAddr)>) {
+    System::run(move || {
+        let count = clients.len();
+        Server::create(move |ctx| {
+            RakServer::new_sharded(listener, 0x1919, "sharding".to_owned(), ctx.address(), 2);
+            Server {
+                clients: count,
+                peers: HashSet::new(),
+            }
+        });
+        for (guid, (local, server_addr)) in clients.into_iter().enumerate() {
+            let socket = tokio::net::UdpSocket::from_std(UdpSocket::bind(local).unwrap()).unwrap();
+            Client::create(move |ctx| {
+                let rak_client = RakClient::init(
+                    socket,
+                    guid as u64,
+                    ctx.address(),
+                    System::current().arbiter(),
+                );
+                rak_client.connect(server_addr);
+                Client { rak_client }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn reuseport_shards() {
+    let listener = Listener::bind("127.0.0.1:19151".parse().unwrap(), 4).unwrap();
+    assert_eq!(listener.shards(), 4);
+    let server_addr = listener.local_addrs()[0];
+    let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    run(listener, vec![(local, server_addr); 8]);
+}
+
+#[test]
+fn dual_stack() {
+    let listener = Listener::dual_stack(19152, 2).unwrap();
+    assert_eq!(listener.shards(), 4);
+    run(
+        listener,
+        vec![
+            (
+                "127.0.0.1:0".parse().unwrap(),
+                "127.0.0.1:19152".parse().unwrap(),
+            ),
+            ("[::1]:0".parse().unwrap(), "[::1]:19152".parse().unwrap()),
+        ],
+    );
+}
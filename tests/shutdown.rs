@@ -0,0 +1,86 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::RakClientEvent,
+    server::{RakServer, RakServerEvent, Shutdown},
+};
+use futures::executor::block_on;
+
+mod common;
+
+use common::{create_client_with, Client, Connect};
+
+struct Server {
+    rak_server: Addr<RakServer<Self>>,
+    disconnected: Arc<AtomicBool>,
+}
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerEvent::Packet(_, _) = msg {
+            let rak_server = self.rak_server.clone();
+            let disconnected = self.disconnected.clone();
+            actix::spawn(async move {
+                let started = Instant::now();
+                let shutdown = Shutdown {
+                    timeout: Duration::from_secs(3),
+                };
+                rak_server.send(shutdown).await.unwrap();
+                assert!(started.elapsed() < Duration::from_secs(3));
+                actix::clock::delay_for(Duration::from_millis(100)).await;
+                assert!(disconnected.load(Ordering::SeqCst));
+                System::current().stop();
+            });
+        }
+    }
+}
+
+async fn create_client(guid: u64, addr: SocketAddr, disconnected: Arc<AtomicBool>) -> Addr<Client> {
+    create_client_with(guid, addr, move |msg| {
+        if let RakClientEvent::Disconnected = msg {
+            disconnected.store(true, Ordering::SeqCst);
+        }
+    })
+    .await
+}
+
+async fn create_server(
+    guid: u64,
+    addr: SocketAddr,
+    motd: String,
+    disconnected: Arc<AtomicBool>,
+) -> Addr<Server> {
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    Server::create(|ctx| {
+        let rak_server = RakServer::new(socket, guid, motd, ctx.address(), 1);
+        Server {
+            rak_server,
+            disconnected,
+        }
+    })
+}
+
+#[test]
+fn shutdown() {
+    System::run(||{
+        let disconnected = Arc::new(AtomicBool::new(false));
+        let server_addr: SocketAddr = "127.0.0.1:19148".parse().unwrap();
+        block_on(create_server(0x1919, server_addr, "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned(), disconnected.clone()));
+
+        let client1_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let client1 = block_on(create_client(114514, client1_addr, disconnected));
+        client1.do_send(Connect(server_addr));
+    }).unwrap();
+}
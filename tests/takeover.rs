@@ -0,0 +1,347 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_raknet::{
+    client::{ClientHandle, ConnectionFailedReason, RakClient, RakClientEvent},
+    packets::{encode, ConnectionRequest, Frame, FrameSet, OpenConnectionRequest2, Reliability},
+    server::{
+        RakServer, RakServerEvent, RakServerNotice, SetNotices, SetTakeoverPolicy, TakeoverPolicy,
+    },
+    transport::MemoryNetwork,
+};
+use futures::executor::block_on;
+
+const SERVER: &str = "10.0.0.1:19132";
+const CRASHED: &str = "10.0.0.2:40000";
+const RESTARTED: &str = "10.0.0.2:40001";
+const GUID: u64 = 114514;
+
+struct Client {
+    network: MemoryNetwork,
+    restarted: bool,
+    rejected: bool,
+    rak_clients: Vec<ClientHandle>,
+}
+
+impl Actor for Client {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakClientEvent> for Client {
+    type Result = ();
+    fn handle(&mut self, msg: RakClientEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakClientEvent::Connected if !self.restarted => {
+                self.restarted = true;
+                let rak_client = RakClient::init_in_memory(
+                    &self.network,
+                    RESTARTED.parse().unwrap(),
+                    GUID,
+                    ctx.address(),
+                    System::current().arbiter(),
+                );
+                rak_client.connect(SERVER.parse().unwrap());
+                self.rak_clients.push(rak_client);
+            }
+            RakClientEvent::ConnectionFailed(ConnectionFailedReason::AlreadyConnected) => {
+                assert!(self.rejected);
+                System::current().stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Server {
+    replaced: bool,
+    disconnected: bool,
+    reconnected: bool,
+}
+
+impl Server {
+    fn check(&self) {
+        if self.replaced && self.disconnected && self.reconnected {
+            System::current().stop();
+        }
+    }
+}
+
+impl Actor for Server {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            RakServerEvent::Disconnected(addr, guid) => {
+                assert_eq!(addr, CRASHED.parse().unwrap());
+                assert_eq!(guid, GUID);
+                self.disconnected = true;
+            }
+            RakServerEvent::Connected(handle) if handle.address == RESTARTED.parse().unwrap() => {
+                assert_eq!(handle.guid, GUID);
+                self.reconnected = true;
+            }
+            _ => {}
+        }
+        self.check();
+    }
+}
+
+impl Handler<RakServerNotice> for Server {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerNotice, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerNotice::Replaced(addr, guid) = msg {
+            assert_eq!(addr, CRASHED.parse().unwrap());
+            assert_eq!(guid, GUID);
+            self.replaced = true;
+        }
+        self.check();
+    }
+}
+
+fn run(policy: TakeoverPolicy, rejected: bool) {
+    System::run(move || {
+        let network = MemoryNetwork::new();
+        let server_network = network.clone();
+        Server::create(move |ctx| {
+            let rak_server = RakServer::new_in_memory(
+                &server_network,
+                SERVER.parse().unwrap(),
+                0x1919,
+                "takeover".to_owned(),
+                ctx.address(),
+                1,
+            );
+            rak_server.do_send(SetTakeoverPolicy(policy));
+            rak_server.do_send(SetNotices(Some(ctx.address().recipient())));
+            Server {
+                replaced: false,
+                disconnected: false,
+                reconnected: false,
+            }
+        });
+        Client::create(move |ctx| {
+            let address: SocketAddr = CRASHED.parse().unwrap();
+            let rak_client = RakClient::init_in_memory(
+                &network,
+                address,
+                GUID,
+                ctx.address(),
+                System::current().arbiter(),
+            );
+            rak_client.connect(SERVER.parse().unwrap());
+            Client {
+                network,
+                restarted: false,
+                rejected,
+                rak_clients: vec![rak_client],
+            }
+        });
+    })
+    .unwrap();
+}
+
+#[test]
+fn replace_stale_session() {
+    run(TakeoverPolicy::Replace, false);
+}
+
+#[test]
+fn reject_active_session() {
+    run(
+        TakeoverPolicy::RejectIfActive(Duration::from_secs(30)),
+        true,
+    );
+}
+
+struct Observer {
+    replaced: Arc<AtomicBool>,
+}
+
+impl Actor for Observer {
+    type Context = Context<Self>;
+}
+
+impl Handler<RakServerEvent> for Observer {
+    type Result = ();
+    fn handle(&mut self, _msg: RakServerEvent, _ctx: &mut Self::Context) -> Self::Result {}
+}
+
+impl Handler<RakServerNotice> for Observer {
+    type Result = ();
+    fn handle(&mut self, msg: RakServerNotice, _ctx: &mut Self::Context) -> Self::Result {
+        if let RakServerNotice::Replaced(..) = msg {
+            self.replaced.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+fn retransmit(policy: TakeoverPolicy, port: u16) {
+    let replies = Arc::new(Mutex::new(Vec::new()));
+    let replaced = Arc::new(AtomicBool::new(false));
+    let server_replies = replies.clone();
+    let server_replaced = replaced.clone();
+    System::run(move || {
+        let server_addr: SocketAddr = ([127, 0, 0, 1], port).into();
+        let socket = block_on(tokio::net::UdpSocket::bind(server_addr)).unwrap();
+        Observer::create(move |ctx| {
+            let rak_server =
+                RakServer::new(socket, 0x1919, "takeover".to_owned(), ctx.address(), 1);
+            rak_server.do_send(SetTakeoverPolicy(policy));
+            rak_server.do_send(SetNotices(Some(ctx.address().recipient())));
+            Observer {
+                replaced: server_replaced,
+            }
+        });
+        let mut client = block_on(tokio::net::UdpSocket::bind(SocketAddr::from((
+            [127, 0, 0, 1],
+            0,
+        ))))
+        .unwrap();
+        tokio::spawn(async move {
+            let request2 = encode(OpenConnectionRequest2::new(server_addr, 1400, GUID));
+            for _ in 0..2 {
+                client.send_to(&request2, server_addr).await.unwrap();
+                let mut buff = [0u8; 1500];
+                let (_, _) = client.recv_from(&mut buff).await.unwrap();
+                server_replies.lock().unwrap().push(buff[0]);
+            }
+            actix::clock::delay_for(Duration::from_millis(100)).await;
+            System::current().stop();
+        });
+    })
+    .unwrap();
+    assert_eq!(*replies.lock().unwrap(), vec![0x08, 0x08]);
+    assert!(!replaced.load(Ordering::SeqCst));
+}
+
+#[test]
+fn retransmitted_request2_reject() {
+    retransmit(TakeoverPolicy::Reject, 19153);
+}
+
+#[test]
+fn retransmitted_request2_replace() {
+    retransmit(TakeoverPolicy::Replace, 19154);
+}
+
+fn reconnect(policy: TakeoverPolicy, port: u16) -> (Vec<u8>, bool) {
+    let replies = Arc::new(Mutex::new(Vec::new()));
+    let replaced = Arc::new(AtomicBool::new(false));
+    let server_replies = replies.clone();
+    let server_replaced = replaced.clone();
+    System::run(move || {
+        let server_addr: SocketAddr = ([127, 0, 0, 1], port).into();
+        let socket = block_on(tokio::net::UdpSocket::bind(server_addr)).unwrap();
+        Observer::create(move |ctx| {
+            let rak_server =
+                RakServer::new(socket, 0x1919, "takeover".to_owned(), ctx.address(), 1);
+            rak_server.do_send(SetTakeoverPolicy(policy));
+            rak_server.do_send(SetNotices(Some(ctx.address().recipient())));
+            Observer {
+                replaced: server_replaced,
+            }
+        });
+        let mut client = block_on(tokio::net::UdpSocket::bind(SocketAddr::from((
+            [127, 0, 0, 1],
+            0,
+        ))))
+        .unwrap();
+        tokio::spawn(async move {
+            let request2 = encode(OpenConnectionRequest2::new(server_addr, 1400, GUID));
+            let request = FrameSet {
+                header: 0x84,
+                sequence_number: 0,
+                datas: vec![Frame::new(
+                    Reliability::ReliableOrdered,
+                    encode(ConnectionRequest::new(GUID, 0, false)),
+                )],
+            };
+            client.send_to(&request2, server_addr).await.unwrap();
+            client
+                .send_to(&request.encode(), server_addr)
+                .await
+                .unwrap();
+            actix::clock::delay_for(Duration::from_millis(100)).await;
+            // the session is past its handshake, the same request is a reconnect now
+            client.send_to(&request2, server_addr).await.unwrap();
+            let mut buff = [0u8; 1500];
+            while let Ok(Ok(_)) =
+                tokio::time::timeout(Duration::from_millis(500), client.recv_from(&mut buff)).await
+            {
+                if buff[0] & 0x80 == 0 {
+                    server_replies.lock().unwrap().push(buff[0]);
+                }
+            }
+            System::current().stop();
+        });
+    })
+    .unwrap();
+    let replies = replies.lock().unwrap().clone();
+    (replies, replaced.load(Ordering::SeqCst))
+}
+
+#[test]
+fn reconnect_from_same_address_reject() {
+    assert_eq!(
+        reconnect(TakeoverPolicy::Reject, 19157),
+        (vec![0x08, 0x12], false)
+    );
+}
+
+#[test]
+fn reconnect_from_same_address_replace() {
+    assert_eq!(
+        reconnect(TakeoverPolicy::Replace, 19158),
+        (vec![0x08, 0x08], true)
+    );
+}
+
+#[test]
+fn cookie_without_migration() {
+    let replies = Arc::new(Mutex::new(Vec::new()));
+    let server_replies = replies.clone();
+    System::run(move || {
+        let server_addr: SocketAddr = ([127, 0, 0, 1], 19155).into();
+        let socket = block_on(tokio::net::UdpSocket::bind(server_addr)).unwrap();
+        Observer::create(move |ctx| {
+            RakServer::new(socket, 0x1919, "takeover".to_owned(), ctx.address(), 1);
+            Observer {
+                replaced: Arc::new(AtomicBool::new(false)),
+            }
+        });
+        let bind = || {
+            block_on(tokio::net::UdpSocket::bind(SocketAddr::from((
+                [127, 0, 0, 1],
+                0,
+            ))))
+            .unwrap()
+        };
+        let clients = vec![bind(), bind()];
+        tokio::spawn(async move {
+            let request2 = OpenConnectionRequest2::new(server_addr, 1400, GUID);
+            let requests = [encode(request2.clone()), encode(request2.with_cookie(7))];
+            for (mut client, request2) in clients.into_iter().zip(requests) {
+                client.send_to(&request2, server_addr).await.unwrap();
+                let mut buff = [0u8; 1500];
+                let received = client.recv_from(&mut buff);
+                if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_millis(500), received).await
+                {
+                    server_replies.lock().unwrap().push(buff[0]);
+                }
+            }
+            System::current().stop();
+        });
+    })
+    .unwrap();
+    assert_eq!(*replies.lock().unwrap(), vec![0x08, 0x12]);
+}